@@ -88,6 +88,15 @@ pub fn collide_entity_terrain(
 // ok extend this so bullets dont collide
 // and no friendly fire
 
+fn resting_ordnance(e: &Entity, other_key: u32) -> bool {
+    match e.fuse {
+        Some(fuse) if e.kind == EntityKind::Bullet => {
+            fuse.stuck_to.map(|(host, _)| host == other_key).unwrap_or(false) || e.velocity.magnitude() < 0.01
+        },
+        _ => false,
+    }
+}
+
 fn keep_entity_entity_collision(subject_key: u32, object_key: u32, entities: &HashMap<u32, Entity>) -> bool {
     if subject_key == object_key {return false};
     match (entities.get(&subject_key), entities.get(&object_key)) {
@@ -101,10 +110,18 @@ fn keep_entity_entity_collision(subject_key: u32, object_key: u32, entities: &Ha
             if (subject.kind == EntityKind::Bullet || object.kind == EntityKind::Bullet) && subject.team == object.team {
                 return false;
             }
-            if subject.kind == EntityKind::Bullet && object.kind == EntityKind::GunPickup {
+            // piercing bullets pass through whatever theyve already hit
+            if subject.hit_ids.contains(&object_key) || object.hit_ids.contains(&subject_key) {
                 return false;
             }
-            if subject.kind == EntityKind::GunPickup && object.kind != EntityKind::Player {
+            if subject.kind == EntityKind::Bullet && object.kind.is_pickup() {
+                return false;
+            }
+            if subject.kind.is_pickup() && object.kind != EntityKind::Player {
+                return false;
+            }
+            // stuck on or sitting still ordnance is just along for the ride, it doesnt shove anyone
+            if resting_ordnance(subject, object_key) || resting_ordnance(object, subject_key) {
                 return false;
             }
 
@@ -139,6 +156,30 @@ pub fn collide_entity_entity(
     }
 }
 
+// fraction along a->b where the segment enters r, slab test
+pub fn segment_rect_intersection(a: Vec2, b: Vec2, r: Rect) -> Option<f32> {
+    let d = b - a;
+    let mut t_min: f32 = 0.0;
+    let mut t_max: f32 = 1.0;
+
+    for (start, delta, lo, hi) in [(a.x, d.x, r.left(), r.right()), (a.y, d.y, r.top(), r.bot())] {
+        if delta == 0.0 {
+            if start < lo || start > hi {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (lo - start) / delta;
+        let t2 = (hi - start) / delta;
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return None;
+        }
+    }
+    Some(t_min)
+}
+
 fn movement_bounds(subject_key: u32, collisions: &Vec<CollisionEvent>) -> (f32, f32, f32, f32) {
     let max_dx = collisions.iter().filter(|col| col.subject == subject_key)
         .filter(|col| col.penetration.x < 0.0)
@@ -183,6 +224,16 @@ pub fn apply_movement(entities: &mut HashMap<u32, Entity>, collisions: &Vec<Coll
     }
 }
 
+#[test]
+fn test_segment_rect_intersection() {
+    let r = Rect::new(1.0, 1.0, 1.0, 1.0);
+    assert_eq!(segment_rect_intersection(Vec2::new(0.0, 1.5), Vec2::new(4.0, 1.5), r), Some(0.25));
+    assert_eq!(segment_rect_intersection(Vec2::new(0.0, 0.0), Vec2::new(3.0, 0.0), r), None);
+    assert_eq!(segment_rect_intersection(Vec2::new(0.0, 0.0), Vec2::new(0.9, 0.9), r), None);
+    assert_eq!(segment_rect_intersection(Vec2::new(1.5, 1.5), Vec2::new(5.0, 5.0), r), Some(0.0));
+    assert_eq!(segment_rect_intersection(Vec2::new(1.5, 0.0), Vec2::new(1.5, 4.0), r), Some(0.25));
+}
+
 #[test]
 fn test_collide_rects() {
     {
@@ -0,0 +1,73 @@
+use glam::Vec3;
+use crate::kmath::*;
+use crate::renderer::*;
+
+// short lived visual stuff, game pushes these and draws them until they expire
+
+#[derive(Debug, Clone, Copy)]
+pub enum EffectKind {
+    Ring(Vec2, f32),    // centre, max radius
+    Arc(Vec2, Vec2),
+    Beam(Vec2, Vec2, Vec3),     // from, to, colour
+    Hit(Vec2, bool),            // where, crit
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub t_start: f32,
+    pub duration: f32,
+}
+
+impl Effect {
+    pub fn new(kind: EffectKind, t_start: f32, duration: f32) -> Effect {
+        Effect {
+            kind,
+            t_start,
+            duration,
+        }
+    }
+
+    pub fn expired(&self, t: f32) -> bool {
+        t - self.t_start > self.duration
+    }
+
+    pub fn draw(&self, renderer: &mut Renderer, t: f32, height: f32) {
+        let progress = ((t - self.t_start) / self.duration).min(1.0);
+        match self.kind {
+            EffectKind::Ring(centre, max_radius) => {
+                let radius = max_radius * progress;
+                let colour = Vec3::new(1.0, 0.6 * (1.0 - progress), 0.0);
+                let segments = 24;
+                for i in 0..segments {
+                    let a = Vec2::new(radius, 0.0).rotate(i as f32 / segments as f32 * 2.0 * std::f32::consts::PI);
+                    let b = Vec2::new(radius, 0.0).rotate((i + 1) as f32 / segments as f32 * 2.0 * std::f32::consts::PI);
+                    renderer.draw_line(centre + a, centre + b, 0.01, colour, height);
+                }
+            },
+            EffectKind::Arc(a, b) => {
+                // bit of jitter so it crackles
+                let mid = a.lerp(b, 0.5) + Vec2::new(0.0, 0.02).rotate(t * 97.0);
+                let colour = Vec3::new(0.6, 0.8, 1.0);
+                renderer.draw_line(a, mid, 0.006, colour, height);
+                renderer.draw_line(mid, b, 0.006, colour, height);
+            },
+            EffectKind::Beam(a, b, colour) => {
+                renderer.draw_line(a, b, 0.012 * (1.0 - progress), colour, height);
+            },
+            EffectKind::Hit(pos, crit) => {
+                // little spark, crits get a bigger yellow star
+                let (spokes, size, colour) = if crit {
+                    (8, 0.05, Vec3::new(1.0, 0.9, 0.1))
+                } else {
+                    (4, 0.02, Vec3::new(1.0, 1.0, 1.0))
+                };
+                let r = size * (0.5 + 0.5 * progress);
+                for i in 0..spokes {
+                    let d = Vec2::new(r, 0.0).rotate(0.4 + i as f32 / spokes as f32 * 2.0 * std::f32::consts::PI);
+                    renderer.draw_line(pos + d * 0.5, pos + d, 0.004, colour, height);
+                }
+            },
+        }
+    }
+}
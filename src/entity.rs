@@ -2,6 +2,11 @@ use crate::rect::*;
 use crate::gun::*;
 use crate::level::*;
 use crate::kmath::*;
+use crate::status::*;
+use crate::melee::*;
+use crate::spell::*;
+use crate::resource::*;
+use crate::ordnance::*;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum EntityKind {
@@ -9,8 +14,16 @@ pub enum EntityKind {
     WalkerShooter,
     RunnerGunner,
     Chungus,
+    Rusher,
     Bullet,
     GunPickup,
+    SpellPage,
+}
+
+impl EntityKind {
+    pub fn is_pickup(&self) -> bool {
+        *self == EntityKind::GunPickup || *self == EntityKind::SpellPage
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -19,10 +32,14 @@ pub enum EntityTeam {
     Enemy,
 }
 
+// aim is separate from the trigger so a burst thats still going follows where youre looking
 pub enum EntityCommand {
     Move(u32, Vec2),
-    Shoot(u32, Vec2),
+    Aim(u32, Vec2),
+    Shoot(u32),
     Unshoot(u32),
+    Reload(u32),
+    Melee(u32),
 }
 
 #[derive(Debug, Clone)]
@@ -34,7 +51,11 @@ pub struct Entity {
 
     pub gun: Gun,
     pub want_shoot: bool,
-    pub previous_shoot_dir: Vec2,
+    pub want_reload: bool,
+    pub aim_dir: Vec2,
+    pub melee: Option<Melee>,
+    pub want_melee: bool,
+    pub knockback: Vec2,
 
     pub owner: u32,
     pub team: EntityTeam,
@@ -43,41 +64,96 @@ pub struct Entity {
     pub max_health: f32,
     
     pub damage: f32,
+    pub bounces: i32,
+    pub explosion_radius: f32,
+    pub self_damage: bool,
+    pub chain: i32,
+    pub pierce: i32,
+    pub hit_ids: Vec<u32>,
+    pub homing: f32,
+    pub range: f32,
+    pub falloff: f32,
+    pub travelled: f32,
+    pub variance: f32,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    pub spawn_index: u32,   // which shot this was, keeps its damage rolls reproducible
+    pub ammo_type: AmmoType,
+    pub fuse: Option<Fuse>,
+
+    pub status: Vec<StatusEffect>,
+
+    pub spell: Option<Spell>,
+    pub resources: Resources,
 
 }
 
 impl Entity {
     pub fn new(kind: EntityKind, pos: Vec2) -> Entity {
+        Entity::new_seeded(kind, pos, 0)
+    }
+
+    // seed decides anything procedural about it, eg the gun in a pickup
+    pub fn new_seeded(kind: EntityKind, pos: Vec2, seed: u32) -> Entity {
         let side_length = match kind {
             EntityKind::Player => 0.05,
             EntityKind::WalkerShooter |
             EntityKind::RunnerGunner => 0.05,
             EntityKind::Chungus => 0.1,
+            EntityKind::Rusher => 0.04,
             EntityKind::Bullet => 0.02,
             EntityKind::GunPickup => 0.04,
+            EntityKind::SpellPage => 0.04,
         };
         let gun = match kind {
             EntityKind::Player => {Gun::new_burstrifle()},
+            // some walkers lob grenades instead
+            EntityKind::WalkerShooter if khash(seed) % 4 == 1 => {
+                Gun::new(2.0, 2.5, 0.5, 0.1, 0)
+                    .with_delivery(Delivery::Fused(FuseKind::Grenade, 1.0))
+                    .with_heat(0.4, 0.3)
+            }
             EntityKind::WalkerShooter => {
-                Gun::new(0.5, 1.1, 0.6, 0.05, 9999999)
+                Gun::new(1.0, 1.1, 0.6, 0.05, 0)
+                    .with_heat(0.2, 0.3)
             }
+            // and some runners fire seekers
+            EntityKind::RunnerGunner if khash(seed) % 4 == 1 => {Gun::new_seeker()},
             EntityKind::RunnerGunner => {
-                Gun::new(0.5, 0.05, 0.6, 0.2, 9999999)
+                Gun::new(1.0, 0.05, 0.6, 0.2, 0)
                     .with_burst(5, 1.5)
+                    .with_heat(0.1, 0.4)
             }
             EntityKind::Chungus => {
-                Gun::new(0.5, 0.1, 0.5, 0.3, 9999999)
+                // either a spiral or pulsing rings. the pattern is the spread, so one bullet
+                // per emitter and no wobble or it turns to mush
+                let pattern = match khash(seed) % 2 {
+                    0 => Pattern::Spiral(4, 2.0),
+                    _ => Pattern::Ring(12),
+                };
+                Gun::new(1.0, 0.1, 0.5, 0.0, 0)
                     .with_burst(4, 4.0)
-                    .with_multishot(5, 0.5)
+                    .with_pattern(pattern)
+                    .with_heat(0.15, 0.3)
             }
-            EntityKind::GunPickup => {generate_gun(3)}
+            EntityKind::GunPickup => {generate_gun(3, seed)}
             _ => {Gun::new(1.0, 1.0, 1.0, 1.0, 1)}
         };
+        let spell = match kind {
+            EntityKind::SpellPage => Some(Spell::random(seed)),
+            _ => None,
+        };
+        let melee = match kind {
+            EntityKind::Player => Some(Melee::new_knife()),
+            EntityKind::Rusher => Some(Melee::new_claws()),
+            _ => None,
+        };
         let speed = match kind {
             EntityKind::Player => 0.7,
             EntityKind::WalkerShooter => 0.2,
             EntityKind::RunnerGunner => 0.3,
             EntityKind::Chungus => 0.15,
+            EntityKind::Rusher => 0.45,
             _ => 0.0,
         };
         let team = match kind {
@@ -89,6 +165,7 @@ impl Entity {
             EntityKind::Player => 4.0,
             EntityKind::RunnerGunner |
             EntityKind::WalkerShooter => 4.0,
+            EntityKind::Rusher => 2.0,
             _ => 1.0,
         };
 
@@ -98,16 +175,45 @@ impl Entity {
             velocity: Vec2::new(0.0, 0.0),
             gun: gun,
             want_shoot: false,
-            previous_shoot_dir: Vec2::new(1.0, 0.0),
+            want_reload: false,
+            aim_dir: Vec2::new(1.0, 0.0),
+            melee,
+            want_melee: false,
+            knockback: Vec2::new(0.0, 0.0),
             owner: 123123, // sentinel
             health: health,
             max_health: health,
             damage: 0.0,
+            bounces: 0,
+            explosion_radius: 0.0,
+            self_damage: false,
+            chain: 0,
+            pierce: 0,
+            hit_ids: Vec::new(),
+            homing: 0.0,
+            range: 2.5,
+            falloff: 0.0,
+            travelled: 0.0,
+            variance: 0.0,
+            crit_chance: 0.0,
+            crit_multiplier: 1.0,
+            spawn_index: 0,
+            ammo_type: AmmoType::Standard,
+            fuse: None,
+            status: Vec::new(),
+            spell,
+            resources: Resources::new(),
             speed: speed,
             team: team,
         }
     }
 
+    pub fn with_size(mut self, side_length: f32) -> Entity {
+        let c = self.aabb.centroid();
+        self.aabb = Rect::new_centered(c.x, c.y, side_length, side_length);
+        self
+    }
+
     pub fn with_velocity(mut self, velocity: Vec2) -> Entity {
         self.velocity = velocity;
         self
@@ -128,6 +234,121 @@ impl Entity {
         self
     }
 
+    pub fn with_bounces(mut self, bounces: i32) -> Entity {
+        self.bounces = bounces;
+        self
+    }
+
+    pub fn with_explosion(mut self, radius: f32, self_damage: bool) -> Entity {
+        self.explosion_radius = radius;
+        self.self_damage = self_damage;
+        self
+    }
+
+    pub fn with_chain(mut self, chain: i32) -> Entity {
+        self.chain = chain;
+        self
+    }
+
+    pub fn with_pierce(mut self, pierce: i32) -> Entity {
+        self.pierce = pierce;
+        self
+    }
+
+    pub fn with_homing(mut self, turn_rate: f32) -> Entity {
+        self.homing = turn_rate;
+        self
+    }
+
+    pub fn with_ammo_type(mut self, ammo_type: AmmoType) -> Entity {
+        self.ammo_type = ammo_type;
+        self
+    }
+
+    pub fn with_range(mut self, range: f32, falloff: f32) -> Entity {
+        self.range = range;
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_crits(mut self, variance: f32, crit_chance: f32, crit_multiplier: f32) -> Entity {
+        self.variance = variance;
+        self.crit_chance = crit_chance;
+        self.crit_multiplier = crit_multiplier;
+        self
+    }
+
+    pub fn with_fuse(mut self, fuse: Option<Fuse>) -> Entity {
+        self.fuse = fuse;
+        self
+    }
+
+    // bullet damage after falloff, at some distance along its flight
+    pub fn damage_at(&self, distance: f32) -> f32 {
+        self.damage * (1.0 - self.falloff * (distance / self.range).min(1.0))
+    }
+
+    pub fn roll_hit(&self, distance: f32, seed: u32) -> DamageRoll {
+        roll_damage(self.damage_at(distance), self.variance, self.crit_chance, self.crit_multiplier, seed)
+    }
+
+    pub fn apply_damage(&mut self, amount: f32) {
+        self.health -= amount;
+    }
+
+    // same kind again just refreshes it
+    pub fn add_status(&mut self, effect: StatusEffect) {
+        match self.status.iter_mut().find(|s| s.kind == effect.kind) {
+            Some(existing) => existing.remaining = existing.remaining.max(effect.remaining),
+            None => self.status.push(effect),
+        }
+    }
+
+    pub fn has_status(&self, kind: StatusKind) -> bool {
+        self.status.iter().any(|s| s.kind == kind)
+    }
+
+    pub fn tick_status(&mut self, dt: f32) {
+        let dot: f32 = self.status.iter().map(|s| s.kind.damage_per_second() * dt).sum();
+        if dot > 0.0 {
+            self.apply_damage(dot);
+        }
+        for s in self.status.iter_mut() {
+            s.remaining -= dt;
+        }
+        self.status.retain(|s| s.remaining > 0.0);
+    }
+
+    pub fn can_afford(&self, cost: Cost) -> bool {
+        match cost {
+            Cost::Free => true,
+            Cost::Mana(amount) => self.resources.mana >= amount,
+            Cost::Blood(amount) => self.health > amount,
+            Cost::Heat(_) => true,  // you can always push it, thats the point
+        }
+    }
+
+    pub fn pay(&mut self, cost: Cost) {
+        match cost {
+            Cost::Free => {},
+            Cost::Mana(amount) => self.resources.mana -= amount,
+            Cost::Blood(amount) => self.apply_damage(amount),
+            Cost::Heat(amount) => {
+                self.resources.heat += amount;
+                // overflow hurts and leaves you stunned
+                if self.resources.heat > self.resources.max_heat {
+                    self.resources.heat = self.resources.max_heat * 0.5;
+                    self.apply_damage(1.0);
+                    self.add_status(StatusEffect { kind: StatusKind::Stunned, remaining: 1.0 });
+                }
+            },
+        }
+    }
+
+    pub fn move_speed_multiplier(&self) -> f32 {
+        self.status.iter().fold(self.gun.move_speed_multiplier(), |acc, s| acc * s.kind.move_speed_multiplier())
+    }
+
     pub fn think(&self, self_id: u32, level: &Level, commands: &mut Vec<EntityCommand>, t: f32) {
         match self.kind {
             EntityKind::Chungus |
@@ -136,13 +357,16 @@ impl Entity {
                     let this_pos = self.aabb.centroid();
                     let target_pos = target.aabb.centroid();
                     let dvec = target_pos - this_pos;
+                    if dvec.magnitude() > 0.0 {
+                        commands.push(EntityCommand::Aim(self_id, dvec.normalize()));
+                    }
                     if dvec.magnitude() < 1.0 && dvec.magnitude() > 0.4 && level.raycast(this_pos, target_pos).is_none() {
                         commands.push(EntityCommand::Move(self_id, dvec.normalize()));
                     } else {
                         commands.push(EntityCommand::Move(self_id, Vec2::new(0.0, 0.0)));
                     }
                     if dvec.magnitude() < 0.5 && level.raycast(this_pos, target_pos).is_none() {
-                        commands.push(EntityCommand::Shoot(self_id, dvec.normalize()));
+                        commands.push(EntityCommand::Shoot(self_id));
                     } else {
                         commands.push(EntityCommand::Unshoot(self_id));
                     }
@@ -153,6 +377,9 @@ impl Entity {
                     let this_pos = self.aabb.centroid();
                     let target_pos = target.aabb.centroid();
                     let dvec = target_pos - this_pos;
+                    if dvec.magnitude() > 0.0 {
+                        commands.push(EntityCommand::Aim(self_id, dvec.normalize()));
+                    }
 
                     let coward_radius = if self.gun.on_burst_cooldown(t) {
                         0.4
@@ -174,13 +401,77 @@ impl Entity {
 
                     // Shooting
                     if dvec.magnitude() < coward_radius + neutral_radius && dvec.magnitude() > coward_radius  && level.raycast(this_pos, target_pos).is_none() {
-                        commands.push(EntityCommand::Shoot(self_id, dvec.normalize()));
+                        commands.push(EntityCommand::Shoot(self_id));
                     } else {
                         commands.push(EntityCommand::Unshoot(self_id));
                     }
                 }
             },
+            EntityKind::Rusher => {
+                for (_, target) in level.entities.iter().filter(|(_, e)| e.kind == EntityKind::Player) {
+                    let this_pos = self.aabb.centroid();
+                    let target_pos = target.aabb.centroid();
+                    let dvec = target_pos - this_pos;
+                    let reach = self.melee.as_ref().map(|m| m.reach).unwrap_or(0.0);
+                    if dvec.magnitude() > 0.0 {
+                        commands.push(EntityCommand::Aim(self_id, dvec.normalize()));
+                    }
+
+                    // just run at them
+                    if dvec.magnitude() < 1.2 && dvec.magnitude() > 0.5 * reach && level.raycast(this_pos, target_pos).is_none() {
+                        commands.push(EntityCommand::Move(self_id, dvec.normalize()));
+                    } else {
+                        commands.push(EntityCommand::Move(self_id, Vec2::new(0.0, 0.0)));
+                    }
+                    if dvec.magnitude() < reach {
+                        commands.push(EntityCommand::Melee(self_id));
+                    }
+                }
+            },
             _ => {},
         };
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_status_refresh_and_tick() {
+    let mut e = Entity::new(EntityKind::WalkerShooter, Vec2::new(0.0, 0.0));
+    let health = e.health;
+    e.add_status(AmmoType::Incendiary.on_hit().unwrap());
+    e.add_status(StatusEffect { kind: StatusKind::Burning, remaining: 1.0 });
+    assert_eq!(e.status.len(), 1);
+    assert_eq!(e.status[0].remaining, 3.0);
+    e.tick_status(1.0);
+    assert!(e.health < health);
+    e.tick_status(2.5);
+    assert!(!e.has_status(StatusKind::Burning));
+}
+
+#[test]
+fn test_heat_overflow() {
+    let mut e = Entity::new(EntityKind::Player, Vec2::new(0.0, 0.0));
+    let health = e.health;
+    e.pay(Cost::Heat(0.8));
+    assert!(e.resources.power_multiplier() > 1.5);
+    assert!(!e.has_status(StatusKind::Stunned));
+    e.pay(Cost::Heat(0.3));
+    assert!(e.has_status(StatusKind::Stunned));
+    assert!(e.health < health);
+    assert!(!e.can_afford(Cost::Blood(e.health)));
+    assert!(e.can_afford(Cost::Mana(1.0)));
+}
+
+#[test]
+fn test_chungus_pattern_shape() {
+    // one bullet per emitter, so a shot is exactly the ring or the spiral arms
+    for seed in 0..4 {
+        let chungus = Entity::new_seeded(EntityKind::Chungus, Vec2::new(0.0, 0.0), seed);
+        let mut bullets = Vec::new();
+        chungus.gun.make_bullets(&mut bullets, Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 1, EntityTeam::Enemy, 0.0);
+        let expected = match chungus.gun.pattern {
+            Pattern::Spiral(arms, _) => arms,
+            Pattern::Ring(count) => count,
+            Pattern::Aimed => 1,
+        };
+        assert_eq!(bullets.len(), expected as usize);
+    }
+}
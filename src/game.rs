@@ -1,7 +1,7 @@
 use glam::{Mat4, Vec3};
 use crate::kmath::*;
 use glow::*;
-use std::collections::VecDeque;
+use std::collections::HashMap;
 use rand::prelude::*;
 
 use crate::level::*;
@@ -10,14 +10,26 @@ use crate::rect::*;
 use crate::entity::*;
 use crate::collision_system::*;
 use crate::gun::*;
+use crate::effect::*;
+use crate::status::*;
+use crate::spell::*;
+use crate::inventory::*;
 
 #[derive(Debug)]
 pub enum InputCommand {
     Look(Vec2),
-    Shoot(Vec2),
+    Shoot,
     Unshoot,
     Move(Vec2),
     EatGun,
+    CraftGun,
+    CycleGun(i32),
+    MutateGun,
+    Reload,
+    Melee,
+    CastSpell(Vec2),
+    NextPage,
+    PrevPage,
 
     Reset,
 }
@@ -32,15 +44,19 @@ pub struct Game {
     level: Level,
     player_id: u32,
     collisions: Vec<CollisionEvent>,
+    effects: Vec<Effect>,
+    spawned: u32,           // numbers each shot for the seeded damage rolls
+    mutations: u32,         // advances the seeded gun mutations
 
-    player_gun_fifo: VecDeque<Gun>,
+    player_guns: GunInventory,
+    player_spellbook: Spellbook,
 
 }
 
 
 #[test]
 fn test_screen_to_world() {
-    let mut game = Game::new(1.0);
+    let mut game = Game::new(1.0, Discipline::Fifo);
     game.player_pos = Vec2::new(5.0, 5.0);
     game.look = Vec2::new(0.5, 0.5); // cursor in middle should cancel out look
     let p = Vec2::new(0.0, 0.0); // top left
@@ -50,13 +66,47 @@ fn test_screen_to_world() {
     assert_eq!(game.screen_to_world(p), Vec2::new(4.4, 4.4));
 }
 
-fn draw_gun_icon(renderer: &mut Renderer, r: Rect, height: f32) {
+#[test]
+fn test_roll_seed_stable() {
+    let game = Game::new(1.0, Discipline::Fifo);
+    let mut a = Entity::new(EntityKind::Bullet, Vec2::new(0.0, 0.0));
+    a.spawn_index = 3;
+    let mut b = a.clone();
+    b.spawn_index = 4;
+    // same shot same roll however many times you ask, other shots and later hits roll differently
+    assert_eq!(game.roll_seed(&a), game.roll_seed(&a.clone()));
+    assert_ne!(game.roll_seed(&a), game.roll_seed(&b));
+    let first = game.roll_seed(&a);
+    a.hit_ids.push(7);
+    assert_ne!(game.roll_seed(&a), first);
+}
+
+fn rarity_colour(rarity: Rarity) -> Vec3 {
+    match rarity {
+        Rarity::Common => Vec3::new(1.0, 1.0, 1.0),
+        Rarity::Rare => Vec3::new(0.5, 0.7, 1.0),
+        Rarity::Epic => Vec3::new(0.8, 0.4, 1.0),
+    }
+}
+
+// page with a coloured sigil, dimmed while its on cooldown
+fn draw_spell_icon(renderer: &mut Renderer, r: Rect, height: f32, spell: &Spell, ready: bool) {
+    renderer.draw_rect(r, Vec3::new(0.0, 0.0, 0.0), height);
+
+    let inner = r.dilate(-0.005);
+    renderer.draw_rect(inner, Vec3::new(0.95, 0.9, 0.75), height + 1.0);
+
+    let sigil = if ready { spell.colour() } else { spell.colour() * 0.3 };
+    renderer.draw_rect(inner.child(0.25, 0.25, 0.5, 0.5), sigil, height + 2.0);
+}
+
+fn draw_gun_icon(renderer: &mut Renderer, r: Rect, height: f32, rarity: Rarity) {
 
     renderer.draw_rect(r, Vec3::new(0.0, 0.0, 0.0), height);
 
     let inner = r.dilate(-0.005);
 
-    renderer.draw_rect(inner, Vec3::new(1.0, 1.0, 1.0), height + 1.0);
+    renderer.draw_rect(inner, rarity_colour(rarity), height + 1.0);
 
     renderer.draw_rect(inner.child(0.1, 0.2, 0.8, 0.3), Vec3::new(0.0, 0.0, 0.0), height + 2.0);
     renderer.draw_rect(inner.child(0.1, 0.2, 0.3, 0.5), Vec3::new(0.0, 0.0, 0.0), height + 2.0);
@@ -71,7 +121,7 @@ impl Game {
         return p + self.player_pos + look_weight*(self.look - 0.5*screen_max) - 0.5*screen_max;
     }
 
-    pub fn new(aspect_ratio: f32) -> Game {
+    pub fn new(aspect_ratio: f32, discipline: Discipline) -> Game {
 
         let mut game = Game {
             seed: 0,
@@ -80,15 +130,19 @@ impl Game {
             look: Vec2::new(0.0, 0.0),
             player_id: 0,
             collisions: Vec::new(),
+            effects: Vec::new(),
+            spawned: 0,
+            mutations: 0,
             t: 0.0,
             player_pos: Vec2::new(0.0, 0.0),
             aspect_ratio,
-            player_gun_fifo: VecDeque::new(),
+            player_guns: GunInventory::new(discipline),
+            player_spellbook: Spellbook::new(),
         };
 
-        game.player_gun_fifo.push_back(generate_gun(3));
-        game.player_gun_fifo.push_back(generate_gun(3));
-        game.player_gun_fifo.push_back(generate_gun(3));
+        game.player_guns.push(generate_gun(3, khash(game.seed + 1)));
+        game.player_guns.push(generate_gun(3, khash(game.seed + 2)));
+        game.player_guns.push(generate_gun(3, khash(game.seed + 3)));
         
         game
     }
@@ -101,6 +155,14 @@ impl Game {
         self.aspect_ratio = aspect_ratio;
 
         self.collisions.clear();
+        let t = self.t;
+        self.effects.retain(|e| !e.expired(t));
+
+        // player aims wherever the cursor is every frame, not just when the mouse moves
+        let aim = self.screen_to_world(self.look) - self.player_pos;
+        if aim.magnitude() > 0.0 {
+            self.level.apply_command(EntityCommand::Aim(self.player_id, aim.normalize()));
+        }
 
         {   // AI time
             let mut commands = Vec::new();
@@ -115,30 +177,91 @@ impl Game {
 
         {   // Shooting
             let mut new_bullets = Vec::new();
+            let mut new_beams = Vec::new();
+            self.level.fire_guns(self.t, dt, &mut new_bullets, &mut new_beams);
 
-            for (entity_key, entity) in self.level.entities.iter_mut() {
-                let will_shoot = entity.gun.will_shoot(entity.want_shoot, self.t);
-                entity.gun.update(entity.want_shoot, will_shoot, self.t);
-                if will_shoot {
-                    entity.gun.make_bullets(&mut new_bullets, entity.aabb.centroid(), entity.previous_shoot_dir, *entity_key, entity.team);
-                }
+            for mut new_bullet in new_bullets {
+                new_bullet.spawn_index = self.next_spawn_index();
+                self.level.entities.insert(rand::thread_rng().gen(), new_bullet);
             }
 
-            for new_bullet in new_bullets {
-                self.level.entities.insert(rand::thread_rng().gen(), new_bullet);
+            // beams resolve right away, the bullet is just carrying the stats
+            for mut beam in new_beams {
+                beam.spawn_index = self.next_spawn_index();
+                let (end, hits) = self.level.fire_beam(&beam, self.roll_seed(&beam));
+                self.push_hit_effects(&hits);
+                if beam.chain > 0 {
+                    if let Some((first_hit, _)) = hits.first() {
+                        for (a, b) in self.level.chain_lightning(*first_hit, beam.damage, beam.chain, beam.team) {
+                            self.effects.push(Effect::new(EffectKind::Arc(a, b), self.t, 0.15));
+                        }
+                    }
+                }
+                self.effects.push(Effect::new(EffectKind::Beam(beam.aabb.centroid(), end, beam.ammo_type.colour()), self.t, 0.08));
             }
         }
 
+        self.level.expire_bullets(dt);
+        self.level.tick_melee(self.t, dt);
+        self.level.tick_status(dt);
+        self.level.steer_homing(dt);
+
         collide_entity_entity(&self.level.entities, &mut self.collisions, dt);
         collide_entity_terrain(&self.level.entities, &self.level.tiles, self.level.grid_size, 
             self.level.side_length as i32, &mut self.collisions, dt);
 
         // handle bullet collisions
+        let mut ricochets: HashMap<u32, (bool, bool)> = HashMap::new();
+        let mut explosions = Vec::new();
+        let mut chains = Vec::new();
+        let mut fuse_contacts = Vec::new();
         for col in self.collisions.iter() {
+            let roll_seed = self.level.entities.get(&col.subject).map(|subject| self.roll_seed(subject)).unwrap_or(0);
             let damage = if let Some(subject) = self.level.entities.get_mut(&col.subject) {
                 if subject.kind == EntityKind::Bullet {
-                    subject.health = 0.0;
-                    Some(subject.damage)
+                    match col.object {
+                        // fused stuff only hurts when it goes off
+                        _ if subject.fuse.is_some() => {
+                            fuse_contacts.push((col.subject, col.object));
+                            None
+                        },
+                        CollisionObject::Terrain(..) if subject.bounces > 0 => {
+                            // flip once per axis even if it clipped a few tiles
+                            let flip = ricochets.entry(col.subject).or_insert((false, false));
+                            if col.penetration.x != 0.0 {
+                                flip.0 = true;
+                            } else {
+                                flip.1 = true;
+                            }
+                            None
+                        },
+                        // each bullet only gets each entity once, and its done once its used up its pierces
+                        CollisionObject::Entity(id) if subject.health <= 0.0 || subject.hit_ids.contains(&id) => None,
+                        _ => {
+                            let damage = subject.damage_at(subject.travelled);
+                            if subject.health > 0.0 && subject.explosion_radius > 0.0 {
+                                explosions.push((subject.aabb.centroid(), subject.explosion_radius, damage,
+                                    subject.team, subject.owner, subject.self_damage));
+                            }
+                            match col.object {
+                                CollisionObject::Entity(id) => {
+                                    if subject.health > 0.0 && subject.chain > 0 {
+                                        chains.push((id, damage, subject.chain, subject.team));
+                                    }
+                                    subject.hit_ids.push(id);
+                                    if subject.pierce > 0 {
+                                        subject.pierce -= 1;
+                                    } else {
+                                        subject.health = 0.0;
+                                    }
+                                },
+                                CollisionObject::Terrain(..) => {
+                                    subject.health = 0.0;
+                                },
+                            }
+                            Some((subject.roll_hit(subject.travelled, roll_seed), subject.ammo_type))
+                        },
+                    }
                 } else {
                     None
                 }
@@ -147,10 +270,14 @@ impl Game {
             };
 
             match damage {
-                Some(damage_amount) => {match col.object {
+                Some((roll, ammo_type)) => {match col.object {
                     CollisionObject::Entity(id) => {
                         if let Some(object) = self.level.entities.get_mut(&id) {
-                            object.health -= damage_amount;
+                            object.apply_damage(roll.amount);
+                            self.effects.push(Effect::new(EffectKind::Hit(object.aabb.centroid(), roll.crit), self.t, 0.2));
+                            if let Some(effect) = ammo_type.on_hit() {
+                                object.add_status(effect);
+                            }
                         }
                     },
                     _ => {},
@@ -159,6 +286,34 @@ impl Game {
             }
         }
 
+        for (id, object) in fuse_contacts {
+            self.level.fuse_contact(id, object);
+        }
+        explosions.extend(self.level.tick_fuses(dt));
+
+        for (pos, radius, damage, team, owner, self_damage) in explosions {
+            self.level.explode(pos, radius, damage, team, owner, self_damage);
+            self.effects.push(Effect::new(EffectKind::Ring(pos, radius), self.t, 0.25));
+        }
+
+        for (first_hit, damage, jumps, team) in chains {
+            for (a, b) in self.level.chain_lightning(first_hit, damage, jumps, team) {
+                self.effects.push(Effect::new(EffectKind::Arc(a, b), self.t, 0.15));
+            }
+        }
+
+        for (id, (flip_x, flip_y)) in ricochets {
+            if let Some(bullet) = self.level.entities.get_mut(&id) {
+                if flip_x {
+                    bullet.velocity.x = -bullet.velocity.x;
+                }
+                if flip_y {
+                    bullet.velocity.y = -bullet.velocity.y;
+                }
+                bullet.bounces -= 1;
+            }
+        }
+
         // handle pickups
         for col in self.collisions.iter() {
             let gun = if col.subject == self.player_id {
@@ -191,12 +346,27 @@ impl Game {
                     _ => {},
                     }
 
-                    self.player_gun_fifo.push_back(pickup_gun);
+                    self.player_guns.push(pickup_gun);
                 },
                 None => {},
             }
         }
 
+        // spell pages go in the book
+        let player_id = self.player_id;
+        for col in self.collisions.iter().filter(|col| col.subject == player_id) {
+            if let CollisionObject::Entity(id) = col.object {
+                if let Some(entity) = self.level.entities.get_mut(&id) {
+                    if entity.kind == EntityKind::SpellPage {
+                        if let Some(spell) = entity.spell.take() {
+                            self.player_spellbook.add_page(spell);
+                        }
+                        entity.health = 0.0;
+                    }
+                }
+            }
+        }
+
         self.level.entities.retain(|_, ent| ent.health > 0.0);
 
         for col in self.collisions.iter().filter(|col| col.subject == self.player_id) {
@@ -206,7 +376,7 @@ impl Game {
         apply_movement(&mut self.level.entities, &self.collisions, dt);
 
         // calculate enemies remaining
-        let remaining_enemies = self.level.entities.iter().map(|(_, e)| e.kind).filter(|ek| *ek != EntityKind::Bullet && *ek != EntityKind::Player && *ek != EntityKind::GunPickup).count();
+        let remaining_enemies = self.level.entities.iter().map(|(_, e)| e.kind).filter(|ek| *ek != EntityKind::Bullet && *ek != EntityKind::Player && !ek.is_pickup()).count();
         //println!("Remaining enemies: {}", remaining_enemies);
 
         if remaining_enemies == 0 {
@@ -282,14 +452,41 @@ impl Game {
                 );
 
 
-                match ent.kind {
-                    EntityKind::Player => renderer.draw_rect(ent_rect, Vec3::new(1.0, 1.0, 1.0), entity_height),
-                    EntityKind::WalkerShooter => renderer.draw_rect(ent_rect, Vec3::new(1.0, 0.0, 0.0), entity_height),
-                    EntityKind::RunnerGunner => renderer.draw_rect(ent_rect, Vec3::new(0.0, 0.0, 1.0), entity_height),
-                    EntityKind::Chungus => renderer.draw_rect(ent_rect, Vec3::new(0.0, 0.0, 0.5), entity_height),
-                    EntityKind::Bullet => renderer.draw_rect(ent_rect, Vec3::new(1.0, 1.0, 0.0), entity_height),
-                    EntityKind::GunPickup => draw_gun_icon(renderer, ent_rect, entity_height),
+                let colour = match ent.kind {
+                    EntityKind::Player => Vec3::new(1.0, 1.0, 1.0),
+                    EntityKind::WalkerShooter => Vec3::new(1.0, 0.0, 0.0),
+                    EntityKind::RunnerGunner => Vec3::new(0.0, 0.0, 1.0),
+                    EntityKind::Chungus => Vec3::new(0.0, 0.0, 0.5),
+                    EntityKind::Rusher => Vec3::new(0.8, 0.0, 0.8),
+                    EntityKind::Bullet => match ent.fuse {
+                        Some(fuse) => fuse.colour(),
+                        None => ent.ammo_type.colour(),
+                    },
+                    EntityKind::GunPickup => {
+                        draw_gun_icon(renderer, ent_rect, entity_height, ent.gun.rarity());
+                        continue;
+                    },
+                    EntityKind::SpellPage => {
+                        if let Some(spell) = ent.spell.as_ref() {
+                            draw_spell_icon(renderer, ent_rect, entity_height, spell, true);
+                        }
+                        continue;
+                    },
                 };
+                renderer.draw_rect(ent_rect, status_tint(colour, &ent.status), entity_height);
+
+                // swing arc while its active
+                if let Some(melee) = ent.melee.as_ref().filter(|m| m.active(self.t)) {
+                    let c = ent.aabb.centroid();
+                    for i in -2..=2 {
+                        let d = melee.state.dir.rotate(melee.arc * i as f32 / 2.0) * melee.reach;
+                        renderer.draw_line(c, c + d, 0.005, Vec3::new(1.0, 1.0, 1.0), entity_height + 1.0);
+                    }
+                }
+            }
+
+            for effect in self.effects.iter() {
+                effect.draw(renderer, self.t, entity_height + 1.0);
             }
         }
 
@@ -347,14 +544,60 @@ impl Game {
             renderer.draw_rect(hp_bar, Vec3::new(1.0, 0.0, 0.0), hud_front_height);
         }
 
+        {   // Mana and heat bars, next to hp. blood is just hp
+            let (mana_percentage, heat_percentage) = if let Some(player) = self.level.entities.get(&self.player_id) {
+                (player.resources.mana / player.resources.max_mana, player.resources.heat / player.resources.max_heat)
+            } else {
+                (0.0, 0.0)
+            };
+
+            let mana_border = Rect::new(0.3, 0.65, 0.15, 0.08).dilate(-0.02);
+            renderer.draw_rect(mana_border, Vec3::new(0.0, 0.0, 0.0), hud_back_height);
+            let mut mana_bar = mana_border.dilate(-0.01);
+            mana_bar.w *= mana_percentage;
+            renderer.draw_rect(mana_bar, Vec3::new(0.2, 0.4, 1.0), hud_front_height);
+
+            // gets redder as it gets close to going critical
+            let heat_border = Rect::new(0.45, 0.65, 0.15, 0.08).dilate(-0.02);
+            renderer.draw_rect(heat_border, Vec3::new(0.0, 0.0, 0.0), hud_back_height);
+            let mut heat_bar = heat_border.dilate(-0.01);
+            heat_bar.w *= heat_percentage;
+            renderer.draw_rect(heat_bar, Vec3::new(1.0, 0.6 * (1.0 - heat_percentage), 0.0), hud_front_height);
+        }
+
+        {   // Spellbook gui, top right so it stays clear of the guns
+            let right = self.aspect_ratio - 0.02;
+            if let Some(spell) = self.player_spellbook.current() {
+                draw_spell_icon(renderer, Rect::new(right - 0.06, 0.02, 0.06, 0.06), hud_front_height, spell, spell.ready(self.t));
+            }
+            // a pip per page, current one is bigger
+            for (i, spell) in self.player_spellbook.pages.iter().enumerate() {
+                let size = if i == self.player_spellbook.current { 0.02 } else { 0.012 };
+                let pip = Rect::new_centered(right - 0.09 - 0.025 * i as f32, 0.05, size, size);
+                renderer.draw_rect(pip.dilate(0.003), Vec3::new(0.0, 0.0, 0.0), hud_back_height);
+                renderer.draw_rect(pip, spell.colour(), hud_front_height);
+            }
+        }
+
         {   // Gun gui
             // current
-            draw_gun_icon(renderer, Rect::new(0.02, 0.02, 0.06, 0.06), hud_front_height);
+            if let Some(player) = self.level.entities.get(&self.player_id) {
+                draw_gun_icon(renderer, Rect::new(0.02, 0.02, 0.06, 0.06), hud_front_height, player.gun.rarity());
+            }
 
-            let ammo_percentage = if let Some(player) = self.level.entities.get(&self.player_id) {
-                player.gun.state.ammo as f32 / player.gun.max_ammo as f32
+            // ammo guns show the magazine (or reload progress), energy guns show heat
+            let (ammo_percentage, ammo_colour) = if let Some(player) = self.level.entities.get(&self.player_id) {
+                match (player.gun.feed, player.gun.reload_progress(self.t)) {
+                    (Feed::Ammo, Some(progress)) => (progress, Vec3::new(0.6, 0.6, 0.6)),
+                    (Feed::Ammo, None) => (player.gun.state.ammo as f32 / player.gun.magazine as f32, Vec3::new(1.0, 1.0, 0.0)),
+                    (Feed::Heat(..), _) => (player.gun.state.heat, if player.gun.state.overheated {
+                        Vec3::new(1.0, 0.0, 0.0)
+                    } else {
+                        Vec3::new(1.0, 0.5, 0.0)
+                    }),
+                }
             } else { 
-                0.0 
+                (0.0, Vec3::new(1.0, 1.0, 0.0))
             };
 
             let ammo_border = Rect::new(0.1, 0.03, 0.15, 0.04);
@@ -362,12 +605,34 @@ impl Game {
             let mut ammo_bar = ammo_border.dilate(-0.01);
             ammo_bar.w *= ammo_percentage;
             
-            renderer.draw_rect(ammo_bar, Vec3::new(1.0, 1.0, 0.0), hud_front_height);
+            renderer.draw_rect(ammo_bar, ammo_colour, hud_front_height);
+
+            let reserve_percentage = if let Some(player) = self.level.entities.get(&self.player_id) {
+                if player.gun.feed == Feed::Ammo {
+                    player.gun.state.reserve as f32 / player.gun.max_ammo as f32
+                } else {
+                    0.0
+                }
+            } else {
+                0.0
+            };
+
+            let reserve_border = Rect::new(0.1, 0.07, 0.15, 0.015);
+            renderer.draw_rect(reserve_border, Vec3::new(0.0, 0.0, 0.0), hud_back_height);
+            let mut reserve_bar = reserve_border.dilate(-0.004);
+            reserve_bar.w *= reserve_percentage;
+
+            renderer.draw_rect(reserve_bar, Vec3::new(0.8, 0.8, 0.0), hud_front_height);
 
             let mut ypos = 0.08;
-            for gun in self.player_gun_fifo.iter() {
+            let next = self.player_guns.next_index();
+            for (i, gun) in self.player_guns.iter().enumerate() {
                 ypos += 0.01; // padding
-                draw_gun_icon(renderer, Rect::new(0.02, ypos, 0.04, 0.04), hud_front_height);
+                draw_gun_icon(renderer, Rect::new(0.02, ypos, 0.04, 0.04), hud_front_height, gun.rarity());
+                // mark the one youd get next
+                if next == Some(i) {
+                    renderer.draw_rect(Rect::new(0.065, ypos + 0.015, 0.01, 0.01), Vec3::new(1.0, 1.0, 1.0), hud_front_height);
+                }
                 ypos += 0.04; // padding
             }
         }
@@ -383,20 +648,116 @@ impl Game {
         self.level = Level::new_dla(player, self.seed);
     }
 
+    fn next_spawn_index(&mut self) -> u32 {
+        self.spawned = self.spawned.wrapping_add(1);
+        self.spawned
+    }
+
+    // goes off which shot it is and how many things its already hit, not the order collisions come out of the hashmap
+    fn roll_seed(&self, shot: &Entity) -> u32 {
+        khash(khash(self.seed ^ shot.spawn_index) ^ shot.hit_ids.len() as u32)
+    }
+
+    fn push_hit_effects(&mut self, hits: &[(u32, DamageRoll)]) {
+        for (id, roll) in hits {
+            if let Some(ent) = self.level.entities.get(id) {
+                self.effects.push(Effect::new(EffectKind::Hit(ent.aabb.centroid(), roll.crit), self.t, 0.2));
+            }
+        }
+    }
+
+    fn cast_spell(&mut self, dir: Vec2) {
+        let t = self.t;
+        let mut spell = match self.player_spellbook.current() {
+            Some(spell) if spell.ready(t) => spell.clone(),
+            _ => return,
+        };
+        let (pos, team) = match self.level.entities.get_mut(&self.player_id) {
+            Some(player) if player.can_afford(spell.cost) => {
+                spell.power *= player.resources.power_multiplier();
+                player.pay(spell.cost);
+                (player.aabb.centroid(), player.team)
+            },
+            _ => return,
+        };
+        if let Some(page) = self.player_spellbook.current_mut() {
+            page.last_cast = t;
+        }
+
+        match spell.kind {
+            SpellKind::Projectile => {
+                let mut bolt = Entity::new(EntityKind::Bullet, pos)
+                    .with_size(0.04)
+                    .with_velocity(dir * 0.8)
+                    .with_owner(self.player_id)
+                    .with_team(team)
+                    .with_damage(2.0 * spell.power)
+                    .with_explosion(0.1, false);
+                bolt.spawn_index = self.next_spawn_index();
+                self.level.entities.insert(rand::thread_rng().gen(), bolt);
+            },
+            SpellKind::Beam => {
+                let mut shot = Entity::new(EntityKind::Bullet, pos)
+                    .with_velocity(dir)
+                    .with_owner(self.player_id)
+                    .with_team(team)
+                    .with_damage(1.5 * spell.power)
+                    .with_pierce(2);
+                shot.spawn_index = self.next_spawn_index();
+                let (end, hits) = self.level.fire_beam(&shot, self.roll_seed(&shot));
+                self.push_hit_effects(&hits);
+                self.effects.push(Effect::new(EffectKind::Beam(pos, end, spell.colour()), t, 0.15));
+            },
+            SpellKind::Ward => {
+                if let Some(player) = self.level.entities.get_mut(&self.player_id) {
+                    player.add_status(StatusEffect { kind: StatusKind::Warded, remaining: 2.0 * spell.power });
+                }
+            },
+            SpellKind::Aoe => {
+                let radius = 0.3;
+                self.level.explode(pos, radius, 2.0 * spell.power, team, self.player_id, false);
+                self.effects.push(Effect::new(EffectKind::Ring(pos, radius), t, 0.25));
+            },
+            SpellKind::Blink => {
+                // stop just short of any wall in the way
+                let mut dest = pos + dir * 0.4 * spell.power;
+                if let Some(wall) = self.level.raycast(pos, dest) {
+                    dest = wall - dir * 0.05;
+                }
+                if let Some(player) = self.level.entities.get_mut(&self.player_id) {
+                    player.aabb = player.aabb.translate(dest - pos);
+                }
+            },
+        }
+    }
+
     pub fn apply_command(&mut self, cmd: InputCommand) {
         match cmd {
             InputCommand::Look(p) => {
                 self.look = p
             },
-            InputCommand::Shoot(normalized_pos) => {
-                let shoot_pos_world = self.screen_to_world(normalized_pos);
-                let dir = (shoot_pos_world - self.player_pos).normalize();
-
-                self.level.apply_command(EntityCommand::Shoot(self.player_id, dir));
+            InputCommand::Shoot => {
+                self.level.apply_command(EntityCommand::Shoot(self.player_id));
             },
             InputCommand::Unshoot => {
                 self.level.apply_command(EntityCommand::Unshoot(self.player_id));
             },
+            InputCommand::Reload => {
+                self.level.apply_command(EntityCommand::Reload(self.player_id));
+            },
+            InputCommand::CastSpell(normalized_pos) => {
+                let dir = (self.screen_to_world(normalized_pos) - self.player_pos).normalize();
+                self.cast_spell(dir);
+            },
+            InputCommand::NextPage => {
+                self.player_spellbook.next_page();
+            },
+            InputCommand::PrevPage => {
+                self.player_spellbook.prev_page();
+            },
+            InputCommand::Melee => {
+                self.level.apply_command(EntityCommand::Melee(self.player_id));
+            },
             InputCommand::Move(dir) => {
                 self.level.apply_command(EntityCommand::Move(self.player_id, dir));
             },
@@ -405,7 +766,7 @@ impl Game {
             },
             InputCommand::EatGun => {
                 if let Some(player) = self.level.entities.get_mut(&self.player_id) {
-                    if let Some(next_gun) = self.player_gun_fifo.pop_front() {
+                    if let Some(next_gun) = self.player_guns.take() {
                         player.gun = next_gun;
                         player.health += 1.0;
                         println!("New gun: {:?}", player.gun.gun_traits);
@@ -414,7 +775,34 @@ impl Game {
                         }
                     }
                 }
-            }
+            },
+            InputCommand::CraftGun => {
+                if self.player_guns.len() >= 2 {
+                    let a = self.player_guns.take().unwrap();
+                    let b = self.player_guns.take().unwrap();
+                    let child = fuse_guns(&a, &b);
+                    println!("Crafted gun: {:?}", child.gun_traits);
+                    self.player_guns.push(child);
+                }
+            },
+            InputCommand::CycleGun(amount) => {
+                self.player_guns.cycle(amount);
+            },
+            InputCommand::MutateGun => {
+                // costs a bit of hp, the opposite of eating
+                let cost = 1.0;
+                if let Some(player) = self.level.entities.get_mut(&self.player_id) {
+                    if player.health > cost {
+                        if let Some(gun) = self.player_guns.take() {
+                            player.health -= cost;
+                            self.mutations = self.mutations.wrapping_add(1);
+                            let mutant = mutate_gun(&gun, khash((self.seed + 4) ^ khash(self.mutations)));
+                            println!("Mutated gun: {:?} -> {:?}", gun.gun_traits, mutant.gun_traits);
+                            self.player_guns.push(mutant);
+                        }
+                    }
+                }
+            },
         }
     }
 }
@@ -1,10 +1,12 @@
 use crate::kmath::*;
-use rand::prelude::*;
 use crate::entity::*;
-use crate::rect::*;
+use crate::status::*;
+use crate::resource::*;
+use crate::ordnance::*;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub enum GunTrait {
+    Energy,       // ammoless, overheats instead
     Manual,       // Semiauto, +accuracy
     Rapidfire,    // - cooldown - damage
     SprayNPray,   // - cooldown - accuracy
@@ -16,33 +18,110 @@ pub enum GunTrait {
     Sawnoff,      // + quanity + spread + randomspread
     
     TripleShot,   // triple shot - ammo
+    Ricochet,     // bounces off walls - damage
+    Explosive,    // AoE on impact, can hurt you - damage + cooldown
+    Lightning,    // arcs to nearby enemies - damage
+    Piercing,     // passes through enemies - damage
+    Homing,       // steers toward enemies - speed
 
     Marksman,     // + speed + damage + cooldown + accuracy
+
+    Windup,       // charge up, + damage
+
+    Beam,         // hitscan instead of bullets - damage
+
+    Grenade,      // lobbed, goes off after a fuse - speed - cooldown
+    Mine,         // drops mines that wait for someone - speed - cooldown
+    Sticky,       // sticks to what it hits then goes off - cooldown
 }
 
-fn trait_ok(gt: GunTrait, other_traits: &Vec<GunTrait>) -> bool {
-    if gt == GunTrait::HeavyBurst {
-        return other_traits.contains(&GunTrait::DoubleBurst)
+pub struct TraitRule {
+    pub requires: &'static [GunTrait],
+    pub excludes: &'static [GunTrait],
+    pub max_stack: usize,
+    pub priority: i32,      // lower gets applied first, requirements need to be lower than what requires them
+}
+
+impl GunTrait {
+    pub fn rule(&self) -> TraitRule {
+        use GunTrait::*;
+        let (requires, excludes, max_stack, priority): (&'static [GunTrait], &'static [GunTrait], usize, i32) = match self {
+            Energy =>       (&[], &[], 1, 0),
+            Manual =>       (&[], &[SprayNPray, DoubleBurst, Windup], 1, 1),
+            Rapidfire =>    (&[], &[], 2, 2),
+            SprayNPray =>   (&[], &[Manual, Marksman], 2, 3),
+            Hungry =>       (&[], &[], 2, 4),
+            DoubleBurst =>  (&[], &[Manual, Windup], 1, 5),
+            HeavyBurst =>   (&[DoubleBurst], &[], 1, 6),
+            Shotgun =>      (&[], &[], 1, 7),
+            Sawnoff =>      (&[Shotgun], &[Marksman], 1, 8),
+            TripleShot =>   (&[], &[], 1, 9),
+            Ricochet =>     (&[], &[], 2, 10),
+            Explosive =>    (&[], &[], 1, 10),
+            Lightning =>    (&[], &[], 2, 10),
+            Piercing =>     (&[], &[], 2, 10),
+            Homing =>       (&[], &[], 1, 10),
+            Marksman =>     (&[], &[SprayNPray, Sawnoff], 1, 11),
+            Windup =>       (&[], &[Manual, DoubleBurst], 1, 12),
+            Beam =>         (&[], &[Ricochet, Explosive, Homing], 1, 13),
+            // fused stuff never pierces or chains, and a grenade is already explosive
+            Grenade =>      (&[], &[Beam, Ricochet, Homing, Mine, Sticky, Piercing, Lightning, Explosive], 1, 13),
+            Mine =>         (&[], &[Beam, Ricochet, Homing, Sticky, Piercing, Lightning], 1, 13),
+            Sticky =>       (&[], &[Beam, Ricochet, Homing, Piercing, Lightning], 1, 13),
+        };
+        TraitRule { requires, excludes, max_stack, priority }
     }
-    if gt == GunTrait::Sawnoff {
-        return other_traits.contains(&GunTrait::Shotgun)
+}
+
+// can gt go on a gun that already has these
+pub fn trait_ok(gt: GunTrait, other_traits: &[GunTrait]) -> bool {
+    let rule = gt.rule();
+    if !rule.requires.iter().all(|req| other_traits.contains(req)) {
+        return false;
     }
-    true
+    if other_traits.iter().any(|other| rule.excludes.contains(other) || other.rule().excludes.contains(&gt)) {
+        return false;
+    }
+    other_traits.iter().filter(|other| **other == gt).count() < rule.max_stack
+}
+
+// in roll order, dont reorder or seeds will give different guns
+pub const ALL_GUN_TRAITS: [GunTrait; 21] = [
+    GunTrait::Manual,
+    GunTrait::Rapidfire,
+    GunTrait::SprayNPray,
+    GunTrait::Hungry,
+    GunTrait::DoubleBurst,
+    GunTrait::HeavyBurst,
+    GunTrait::Shotgun,
+    GunTrait::Sawnoff,
+    GunTrait::TripleShot,
+    GunTrait::Marksman,
+    GunTrait::Energy,
+    GunTrait::Windup,
+    GunTrait::Ricochet,
+    GunTrait::Explosive,
+    GunTrait::Lightning,
+    GunTrait::Piercing,
+    GunTrait::Homing,
+    GunTrait::Beam,
+    GunTrait::Grenade,
+    GunTrait::Mine,
+    GunTrait::Sticky,
+];
+
+fn random_gun_trait(seed: u32) -> GunTrait {
+    ALL_GUN_TRAITS[(khash(seed) % ALL_GUN_TRAITS.len() as u32) as usize]
 }
 
-fn random_gun_trait() -> GunTrait {
-    match rand::thread_rng().gen_range(0..=9) {
-        0 => GunTrait::Manual,
-        1 => GunTrait::Rapidfire,
-        2 => GunTrait::SprayNPray,
-        3 => GunTrait::Hungry,
-        4 => GunTrait::DoubleBurst,
-        5 => GunTrait::HeavyBurst,
-        6 => GunTrait::Shotgun,
-        7 => GunTrait::Sawnoff,
-        8 => GunTrait::TripleShot,
-        9 => GunTrait::Marksman,
-        _ => panic!("unreachable"),
+// mostly standard
+fn random_ammo_type(seed: u32) -> AmmoType {
+    match khash(seed) % 10 {
+        6 => AmmoType::Incendiary,
+        7 => AmmoType::Cryo,
+        8 => AmmoType::Shock,
+        9 => AmmoType::Toxic,
+        _ => AmmoType::Standard,
     }
 }
 
@@ -55,11 +134,21 @@ pub struct GunState {
     pub burst_count: i32,
     pub repeat: bool,
     pub compelled: bool,
-    pub ammo: i32,
+    pub ammo: i32,          // in the magazine
+    pub reserve: i32,
+    pub reloading: bool,
+    pub reload_start: f32,
+    pub heat: f32,          // 0..1, locks out at 1
+    pub overheated: bool,   // locked out until fully cooled
+    pub charging: bool,
+    pub charge_start: f32,
+    pub charge: f32,        // 0..1, how charged the last shot was
+    pub squeezing: bool,
+    pub trigger_start: f32, // when the trigger was first pulled, patterns evolve from here
 }
 
 impl GunState {
-    pub fn new(ammo: i32) -> GunState {
+    pub fn new(ammo: i32, reserve: i32) -> GunState {
         GunState {
             repeat: false,
             compelled: false,
@@ -67,6 +156,16 @@ impl GunState {
             last_shot: -10000.0,
             last_burst: -10000.0,
             ammo: ammo,
+            reserve,
+            reloading: false,
+            reload_start: -10000.0,
+            heat: 0.0,
+            overheated: false,
+            charging: false,
+            charge_start: -10000.0,
+            charge: 0.0,
+            squeezing: false,
+            trigger_start: -10000.0,
         }
     }
 }
@@ -80,12 +179,30 @@ pub struct Gun {
     pub bullet_size: f32,
     
     pub bullets_per_shot: i32,
+    pub bounces: i32,
+    pub explosion_radius: f32,
+    pub self_damage: bool,
+    pub chain: i32,
+    pub pierce: i32,
+    pub homing: f32,        // max turn rate, radians per second
+    pub range: f32,         // bullets expire after flying this far
+    pub falloff: f32,       // fraction of damage lost by max range
+    pub variance: f32,      // each hit is +- this fraction of the damage
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    pub ammo_type: AmmoType,
+    pub delivery: Delivery,
+    pub pattern: Pattern,
+    pub cost: Cost,         // paid by whoever is holding it, on top of ammo or gun heat
 
     pub max_ammo: i32,
+    pub magazine: i32,
+    pub reload_time: f32,
 
     pub spread: f32,
     
     pub action: Action,
+    pub feed: Feed,
     
     pub state: GunState,
 
@@ -98,22 +215,105 @@ pub enum Action {
     Semi,
     Burst(i32, f32),    // semi burst auto burst?
     Auto,
+    Charge(f32),        // time to full charge, fires on release
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rarity {
+    Common,
+    Rare,
+    Epic,
+}
+
+impl Rarity {
+    pub fn from_power(power: f32) -> Rarity {
+        match power {
+            p if p >= 5.0 => Rarity::Epic,
+            p if p >= 3.2 => Rarity::Rare,
+            _ => Rarity::Common,
+        }
+    }
+
+    pub fn power_band(&self) -> (f32, f32) {
+        match self {
+            Rarity::Common => (0.0, 3.2),
+            Rarity::Rare => (3.2, 5.0),
+            Rarity::Epic => (5.0, f32::INFINITY),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feed {
+    Ammo,
+    Heat(f32, f32),     // heat per shot, heat lost per second
+}
+
+// bullet hell stuff, where each shot goes given how long the trigger has been held
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Aimed,
+    Spiral(i32, f32),       // arms, radians per second
+    Ring(i32),              // bullets, creeps round half a gap per second
+}
+
+impl Pattern {
+    // direction of each emitter for this shot
+    pub fn emitters(&self, dir: Vec2, elapsed: f32) -> Vec<Vec2> {
+        let tau = 2.0 * std::f32::consts::PI;
+        match *self {
+            Pattern::Aimed => vec![dir],
+            Pattern::Spiral(arms, turn_rate) => (0..arms)
+                .map(|i| dir.rotate(elapsed * turn_rate + tau * i as f32 / arms as f32))
+                .collect(),
+            Pattern::Ring(count) => {
+                let gap = tau / count as f32;
+                (0..count).map(|i| dir.rotate(gap * (i as f32 + 0.5 * elapsed))).collect()
+            },
+        }
+    }
+}
+
+// bullets fly, beams hit everything along the line straight away, fused stuff waits then blows up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    Bullet,
+    Beam,
+    Fused(FuseKind, f32),   // kind, fuse time
 }
 
 impl Gun {
     pub fn new_default() -> Gun {
         Gun {
-            damage: 1.0,
+            damage: 2.0,
             cooldown: 0.7,
             bullet_speed: 1.0,
             random_spread: 0.05,
             max_ammo: 50,
+            magazine: 10,
+            reload_time: 1.0,
             bullet_size: 0.02,
             
             bullets_per_shot: 1,
+            bounces: 0,
+            explosion_radius: 0.0,
+            self_damage: false,
+            chain: 0,
+            pierce: 0,
+            homing: 0.0,
+            range: 2.5,
+            falloff: 0.0,
+            variance: 0.1,
+            crit_chance: 0.05,
+            crit_multiplier: 2.0,
+            ammo_type: AmmoType::Standard,
+            delivery: Delivery::Bullet,
+            pattern: Pattern::Aimed,
+            cost: Cost::Free,
             spread: 0.5,
             action: Action::Auto,
-            state: GunState::new(30),
+            feed: Feed::Ammo,
+            state: GunState::new(10, 20),
             gun_traits: Vec::new(),
         }
     }
@@ -121,6 +321,10 @@ impl Gun {
     pub fn apply_trait(&mut self, gun_trait: GunTrait) {
         self.gun_traits.push(gun_trait);
         match gun_trait {
+            GunTrait::Energy => {
+                self.feed = Feed::Heat(0.08, 0.5);
+                self.damage *= 0.9;
+            },
             GunTrait::Manual => {
                 self.action = Action::Semi;
                 self.random_spread *= 0.8;
@@ -129,10 +333,11 @@ impl Gun {
             GunTrait::Rapidfire => {
                 self.cooldown *= 0.4;
                 self.damage *= 0.7;
-                self.max_ammo *= 2;
-                self.state.ammo *= 2;
+                self.scale_capacity(2.0);
             },
             GunTrait::Shotgun => {
+                self.range *= 0.4;
+                self.falloff = (self.falloff + 0.5).min(0.9);
                 self.damage *= 0.7;
                 self.bullets_per_shot += 4;
                 self.spread *= 1.5;
@@ -141,10 +346,11 @@ impl Gun {
             GunTrait::SprayNPray => {
                 self.cooldown *= 0.5;
                 self.random_spread *= 2.0;
+                self.variance += 0.2;
             },
             GunTrait::Hungry => {
-                self.state.ammo /= 2;
-                self.max_ammo /= 2;
+                self.cost = self.cost.plus(Cost::Blood(0.03));
+                self.scale_capacity(0.5);
                 self.cooldown *= 0.8;
                 self.damage *= 1.5;
             },
@@ -154,29 +360,111 @@ impl Gun {
                 self.damage *= 0.7;
             },
             GunTrait::HeavyBurst => {
-                match self.action {
-                    Action::Burst(amount, cooldown) => {
-                        self.action = Action::Burst(amount * 2, cooldown * 2.0);
-                    }
-                    _ => {panic!("unreachable")},
-                };
+                // the rules only roll it after DoubleBurst, but nothing to make heavier otherwise
+                if let Action::Burst(amount, cooldown) = self.action {
+                    self.action = Action::Burst(amount * 2, cooldown * 2.0);
+                }
             },
             GunTrait::TripleShot => {
                 self.bullets_per_shot += 2;
-                self.state.ammo /= 2;
-                self.max_ammo /= 2;
+                self.scale_capacity(0.5);
+            },
+            GunTrait::Ricochet => {
+                self.bounces += 2;
+                self.damage *= 0.85;
+            },
+            GunTrait::Explosive => {
+                self.explosion_radius += 0.15;
+                self.self_damage = true;
+                self.damage *= 0.8;
+                self.cooldown *= 1.2;
+            },
+            GunTrait::Lightning => {
+                self.chain += 3;
+                self.damage *= 0.8;
+            },
+            GunTrait::Piercing => {
+                self.pierce += 2;
+                self.damage *= 0.75;
+            },
+            GunTrait::Homing => {
+                self.homing += 2.0;
+                self.bullet_speed *= 0.8;
             },
             GunTrait::Sawnoff => {
+                self.range *= 0.6;
+                self.falloff = (self.falloff + 0.3).min(0.9);
                 self.random_spread *= 2.0;
                 self.bullets_per_shot += 2;
                 self.spread *= 2.0;
             },
             GunTrait::Marksman => {
+                self.range *= 2.0;
+                self.falloff = (self.falloff * 0.5).clamp(0.0, 0.9);
+                self.crit_chance += 0.2;
+                self.crit_multiplier += 0.5;
                 self.damage *= 1.5;
                 self.cooldown *= 1.5;
                 self.bullet_speed *= 2.0;
                 self.random_spread *= 0.5;
             },
+            GunTrait::Windup => {
+                self.cost = self.cost.plus(Cost::Mana(0.25));
+                self.action = Action::Charge(1.0);
+                self.damage *= 1.2;
+            },
+            GunTrait::Beam => {
+                self.cost = self.cost.plus(Cost::Heat(0.04));
+                self.delivery = Delivery::Beam;
+                self.damage *= 0.8;
+            },
+            GunTrait::Grenade => {
+                self.delivery = Delivery::Fused(FuseKind::Grenade, 0.8);
+                self.explosion_radius = self.explosion_radius.max(DEFAULT_BLAST_RADIUS);
+                self.self_damage = true;
+                self.bullet_speed *= 0.6;
+                self.cooldown *= 2.0;
+            },
+            GunTrait::Mine => {
+                self.delivery = Delivery::Fused(FuseKind::Mine(0.15), 0.5);
+                self.explosion_radius = self.explosion_radius.max(DEFAULT_BLAST_RADIUS);
+                self.bullet_speed *= 0.4;
+                self.cooldown *= 2.5;
+            },
+            GunTrait::Sticky => {
+                self.delivery = Delivery::Fused(FuseKind::Sticky, 1.0);
+                self.explosion_radius = self.explosion_radius.max(DEFAULT_BLAST_RADIUS);
+                self.cooldown *= 1.5;
+            },
+        }
+    }
+
+    // rebuild from scratch, applying in priority order and skipping anything the rules dont allow
+    pub fn from_traits(traits: &[GunTrait]) -> Gun {
+        let mut sorted = traits.to_vec();
+        sorted.sort_by_key(|gt| (gt.rule().priority, *gt));
+
+        let mut g = Gun::new_default();
+        for gt in sorted {
+            if trait_ok(gt, &g.gun_traits) {
+                g.apply_trait(gt);
+            }
+        }
+        g
+    }
+
+    // ammo traits give you more rounds, or less heat per round if its an energy weapon
+    fn scale_capacity(&mut self, factor: f32) {
+        match self.feed {
+            Feed::Ammo => {
+                self.max_ammo = (self.max_ammo as f32 * factor) as i32;
+                self.magazine = ((self.magazine as f32 * factor) as i32).max(1);
+                self.state.ammo = ((self.state.ammo as f32 * factor) as i32).min(self.magazine);
+                self.state.reserve = (self.state.reserve as f32 * factor) as i32;
+            },
+            Feed::Heat(per_shot, dissipation) => {
+                self.feed = Feed::Heat(per_shot / factor, dissipation);
+            },
         }
     }
 
@@ -187,13 +475,31 @@ impl Gun {
             bullet_speed,
             random_spread,
             max_ammo: ammo,
+            magazine: ammo,
+            reload_time: 1.0,
             bullet_size: 0.02,
             
             bullets_per_shot: 1,
+            bounces: 0,
+            explosion_radius: 0.0,
+            self_damage: false,
+            chain: 0,
+            pierce: 0,
+            homing: 0.0,
+            range: 2.5,
+            falloff: 0.0,
+            variance: 0.1,
+            crit_chance: 0.05,
+            crit_multiplier: 2.0,
+            ammo_type: AmmoType::Standard,
+            delivery: Delivery::Bullet,
+            pattern: Pattern::Aimed,
+            cost: Cost::Free,
             spread: 0.0,
             action: Action::Auto,
+            feed: Feed::Ammo,
 
-            state: GunState::new(ammo),
+            state: GunState::new(ammo, 0),
             gun_traits: Vec::new(),
         }
     }
@@ -210,28 +516,106 @@ impl Gun {
         self.action = Action::Semi;
         self
     }
+    pub fn with_homing(mut self, turn_rate: f32) -> Gun {
+        self.homing = turn_rate;
+        self
+    }
+    pub fn with_range(mut self, range: f32, falloff: f32) -> Gun {
+        self.range = range;
+        self.falloff = falloff;
+        self
+    }
+    pub fn with_delivery(mut self, delivery: Delivery) -> Gun {
+        self.delivery = delivery;
+        self
+    }
+    pub fn with_pattern(mut self, pattern: Pattern) -> Gun {
+        self.pattern = pattern;
+        self
+    }
+    pub fn with_ammo_type(mut self, ammo_type: AmmoType) -> Gun {
+        self.ammo_type = ammo_type;
+        self
+    }
+    pub fn with_charge(mut self, charge_time: f32) -> Gun {
+        self.action = Action::Charge(charge_time);
+        self
+    }
+    pub fn with_magazine(mut self, size: i32, reload_time: f32) -> Gun {
+        let total = self.state.ammo + self.state.reserve;
+        self.magazine = size;
+        self.reload_time = reload_time;
+        self.state.ammo = size.min(total);
+        self.state.reserve = total - self.state.ammo;
+        self
+    }
+    pub fn with_cost(mut self, cost: Cost) -> Gun {
+        self.cost = cost;
+        self
+    }
+    pub fn with_heat(mut self, per_shot: f32, dissipation: f32) -> Gun {
+        self.feed = Feed::Heat(per_shot, dissipation);
+        self
+    }
 
 
     pub fn new_machinegun() -> Gun {
-        Gun::new(1.0, 0.05, 1.5, 0.01, 100)
+        Gun::new(2.0, 0.05, 1.5, 0.01, 100)
             .with_multishot(3, 0.5)
     }
     pub fn new_burstrifle() -> Gun {
-        Gun::new(1.0, 0.02, 1.5, 0.01, 100)
+        Gun::new(2.0, 0.02, 1.5, 0.01, 100)
         .with_burst(3, 0.33)
+        .with_magazine(24, 1.2)
     }
     
     pub fn new_shotgun() -> Gun {
-        Gun::new(1.0, 0.7, 1.3, 0.1, 15)
+        Gun::new(2.0, 0.7, 1.3, 0.1, 15)
         .with_multishot(5, 0.5)
         .with_semi_auto()
+        .with_range(1.0, 0.5)
+    }
+
+    pub fn new_seeker() -> Gun {
+        Gun::new(1.0, 0.8, 0.5, 0.1, 0)
+            .with_heat(0.2, 0.3)
+            .with_homing(1.5)
+            .with_cost(Cost::Mana(0.1))
     }
 
     pub fn new_minigun() -> Gun {
-        Gun::new(0.2, 0.01, 1.5, 0.07, 500)
+        Gun::new(0.4, 0.01, 1.5, 0.07, 500)
         //.with_burst(40, 2.0)
     }
 
+    pub fn start_reload(&mut self, t: f32) {
+        if self.feed != Feed::Ammo || self.state.reloading {
+            return;
+        }
+        if self.state.ammo >= self.magazine || self.state.reserve <= 0 {
+            return;
+        }
+        self.state.reloading = true;
+        self.state.reload_start = t;
+    }
+
+    pub fn reload_progress(&self, t: f32) -> Option<f32> {
+        if self.state.reloading {
+            Some(((t - self.state.reload_start) / self.reload_time).min(1.0))
+        } else {
+            None
+        }
+    }
+
+    // reload: movt penalty
+    pub fn move_speed_multiplier(&self) -> f32 {
+        if self.state.reloading {
+            0.5
+        } else {
+            1.0
+        }
+    }
+
     pub fn on_cooldown(&self, t: f32) -> bool {
         t - self.state.last_shot < self.cooldown
     }
@@ -252,7 +636,7 @@ impl Gun {
             return false;
         }
 
-        if self.state.ammo <= 0 {
+        if !self.has_feed() {
             return false;
         }
 
@@ -270,10 +654,44 @@ impl Gun {
             Action::Auto => {
                 squeeze
             },
+            Action::Charge(_) => {
+                !squeeze && self.state.charging
+            },
         }
     }
 
-    pub fn update(&mut self, squeeze: bool, did_shoot: bool, t: f32) {
+    // same but whoever is holding it has to be able to pay for the shot too
+    pub fn will_shoot_for(&self, holder: &Entity, squeeze: bool, t: f32) -> bool {
+        self.will_shoot(squeeze, t) && holder.can_afford(self.cost)
+    }
+
+    fn has_feed(&self) -> bool {
+        match self.feed {
+            Feed::Ammo => self.state.ammo > 0 && !self.state.reloading,
+            Feed::Heat(..) => !self.state.overheated,
+        }
+    }
+
+    pub fn update(&mut self, squeeze: bool, did_shoot: bool, t: f32, dt: f32) {
+        if squeeze && !self.state.squeezing {
+            self.state.trigger_start = t;
+        }
+        self.state.squeezing = squeeze;
+
+        if self.state.reloading && t - self.state.reload_start >= self.reload_time {
+            let loaded = (self.magazine - self.state.ammo).min(self.state.reserve);
+            self.state.ammo += loaded;
+            self.state.reserve -= loaded;
+            self.state.reloading = false;
+        }
+
+        if let Feed::Heat(_, dissipation) = self.feed {
+            self.state.heat = (self.state.heat - dissipation * dt).max(0.0);
+            if self.state.heat == 0.0 {
+                self.state.overheated = false;
+            }
+        }
+
         if !squeeze {
             self.state.repeat = false;
         }
@@ -282,10 +700,36 @@ impl Gun {
             self.state.repeat = true;
         }
 
+        if let Action::Charge(charge_time) = self.action {
+            if did_shoot {
+                self.state.charge = ((t - self.state.charge_start) / charge_time).min(1.0);
+                self.state.charging = false;
+            } else if !squeeze {
+                self.state.charging = false;
+            } else if !self.state.charging && !self.on_cooldown(t) && self.has_feed() {
+                self.state.charging = true;
+                self.state.charge_start = t;
+            }
+        }
+
         if did_shoot {
             self.state.last_shot = t;
 
-            self.state.ammo -= 1;
+            match self.feed {
+                Feed::Ammo => {
+                    self.state.ammo -= 1;
+                    if self.state.ammo <= 0 {
+                        self.start_reload(t);
+                    }
+                },
+                Feed::Heat(per_shot, _) => {
+                    self.state.heat += per_shot;
+                    if self.state.heat >= 1.0 {
+                        self.state.heat = 1.0;
+                        self.state.overheated = true;
+                    }
+                },
+            }
 
             match self.action {
                 Action::Semi => {},
@@ -303,60 +747,433 @@ impl Gun {
 
                 },
                 Action::Auto => {},
+                Action::Charge(_) => {},
             }
         }
     }
 
-    pub fn make_bullets(&self, bullets: &mut Vec<Entity>, pos: Vec2, dir: Vec2, owner_id: u32, owner_team: EntityTeam) {
-        for i in 0..self.bullets_per_shot {
-            let idx = i - self.bullets_per_shot/2; // for 1: 0 for 3: -1 etc
-            let spread_float = self.spread * idx as f32 / self.bullets_per_shot as f32;
-            let spread_dir = dir.rotate(spread_float);
+    // rough expected dps from the stats, good enough to rank guns by
+    pub fn power(&self) -> f32 {
+        let (damage, bullets) = match self.action {
+            Action::Charge(_) => (self.damage * 2.0, (self.bullets_per_shot as f32 * 1.5).round()),
+            _ => (self.damage, self.bullets_per_shot as f32),
+        };
+
+        let shots_per_second = match self.action {
+            Action::Auto => 1.0 / self.cooldown,
+            Action::Semi => 1.0 / self.cooldown.max(0.15), // can only click so fast
+            Action::Burst(n, burst_cooldown) => n as f32 / (n as f32 * self.cooldown + burst_cooldown),
+            Action::Charge(charge_time) => 1.0 / (charge_time + self.cooldown),
+        };
+
+        // multishot spread and inaccuracy both mean less of it lands
+        let spread = if bullets > 1.0 { self.spread } else { 0.0 };
+        let accuracy = 1.0 / (1.0 + 3.0 * self.random_spread + spread);
+
+        let extras = 1.0 + 0.1 * self.bounces as f32 + 0.3 * self.pierce as f32 + 0.2 * self.chain as f32
+            + 3.0 * self.explosion_radius + 0.1 * self.homing;
+
+        // how much of the time you can actually be shooting. goes off capacity not whats loaded
+        // so the score (and rarity) doesnt drop as you fire
+        let uptime = match self.feed {
+            Feed::Ammo => {
+                let mag_time = self.magazine as f32 / shots_per_second;
+                let empty_time = (self.magazine + self.max_ammo) as f32 / shots_per_second;
+                mag_time / (mag_time + self.reload_time) * empty_time / (empty_time + 10.0)
+            },
+            Feed::Heat(per_shot, dissipation) => (dissipation / (per_shot * shots_per_second)).min(1.0),
+        };
+
+        let crits = 1.0 + self.crit_chance * (self.crit_multiplier - 1.0);
+
+        damage * crits * bullets * shots_per_second * accuracy * extras * uptime
+    }
+
+    pub fn rarity(&self) -> Rarity {
+        Rarity::from_power(self.power())
+    }
 
-            let adjusted_dir = spread_dir.spread(self.random_spread);
+    // damage, speed and number of bullets for the next shot
+    pub fn shot_profile(&self) -> (f32, f32, i32) {
+        match self.action {
+            Action::Charge(_) => {
+                let c = self.state.charge;
+                let count = ((self.bullets_per_shot as f32 * (0.5 + c)).round() as i32).max(1);
+                (self.damage * (0.3 + 1.7 * c), self.bullet_speed * (0.6 + 0.8 * c), count)
+            },
+            _ => (self.damage, self.bullet_speed, self.bullets_per_shot),
+        }
+    }
+
+    pub fn make_bullets(&self, bullets: &mut Vec<Entity>, pos: Vec2, dir: Vec2, owner_id: u32, owner_team: EntityTeam, t: f32) {
+        let (damage, bullet_speed, bullets_per_shot) = self.shot_profile();
+        let fuse = match self.delivery {
+            Delivery::Fused(kind, time) => Some(Fuse::new(kind, time)),
+            _ => None,
+        };
+        for emitter_dir in self.pattern.emitters(dir, t - self.state.trigger_start) {
+            for i in 0..bullets_per_shot {
+                let idx = i - bullets_per_shot/2; // for 1: 0 for 3: -1 etc
+                let spread_float = self.spread * idx as f32 / bullets_per_shot as f32;
+                let spread_dir = emitter_dir.rotate(spread_float);
 
-            bullets.push(Entity {
-                kind: EntityKind::Bullet,
-                aabb: Rect::new_centered(pos.x, pos.y, self.bullet_size, self.bullet_size),
-                velocity: adjusted_dir * self.bullet_speed,
-                owner: owner_id,
-                team: owner_team,
-                damage: self.damage,
-                gun: Gun::new_default(),
-                want_shoot: false,
-                health: 1.0,
-                max_health: 1.0,
-                previous_shoot_dir: Vec2::new(0.0, 0.0),
-                speed: 0.0,
-            });
+                let adjusted_dir = spread_dir.spread(self.random_spread);
 
-            bullets.push(Entity::new(EntityKind::Bullet, pos)
-                            .with_velocity(adjusted_dir * self.bullet_speed)
-                            .with_owner(owner_id)
-                            .with_team(owner_team)
-                            .with_damage(self.damage));
+                bullets.push(Entity::new(EntityKind::Bullet, pos)
+                                .with_size(self.bullet_size)
+                                .with_velocity(adjusted_dir * bullet_speed)
+                                .with_owner(owner_id)
+                                .with_team(owner_team)
+                                .with_damage(damage)
+                                .with_bounces(self.bounces)
+                                .with_explosion(self.explosion_radius, self.self_damage)
+                                .with_chain(self.chain)
+                                .with_pierce(self.pierce)
+                                .with_homing(self.homing)
+                                .with_range(self.range, self.falloff)
+                                .with_crits(self.variance, self.crit_chance, self.crit_multiplier)
+                                .with_ammo_type(self.ammo_type)
+                                .with_fuse(fuse));
+            }
         }
     }
 }
 
-pub fn generate_gun(num_traits: i32) -> Gun {
+#[derive(Debug, Clone, Copy)]
+pub struct DamageRoll {
+    pub amount: f32,
+    pub crit: bool,
+}
+
+// seeded so the same seed always gives the same hit
+pub fn roll_damage(base: f32, variance: f32, crit_chance: f32, crit_multiplier: f32, seed: u32) -> DamageRoll {
+    let amount = base * (1.0 + variance * (2.0 * krand(seed) - 1.0));
+    let crit = krand(khash(seed)) < crit_chance;
+    DamageRoll {
+        amount: if crit { amount * crit_multiplier } else { amount },
+        crit,
+    }
+}
+
+pub fn generate_gun(num_traits: i32, mut seed: u32) -> Gun {
     let mut traits = Vec::new();
 
-    for i in 0..num_traits {
-        loop {
-            let gt = random_gun_trait();
+    for _ in 0..num_traits {
+        // give up eventually, a gun could get to where nothing else is allowed
+        for _ in 0..100 {
+            let gt = random_gun_trait(seed);
+            seed = khash(seed.wrapping_add(1));
             if trait_ok(gt, &traits) {
                 traits.push(gt);
                 break;
             }
         }
     }
-    traits.sort();
 
-    let mut g = Gun::new_default();
-    for gt in traits {
-        g.apply_trait(gt);
+    Gun::from_traits(&traits)
+        .with_ammo_type(random_ammo_type(seed))
+}
+
+// rejection sample until its in the band, or give the closest one if we cant find one
+pub fn generate_gun_in_band(num_traits: i32, band: (f32, f32), seed: u32) -> Gun {
+    let (min, max) = band;
+    let distance = |g: &Gun| {
+        let p = g.power();
+        if p < min { min - p } else if p > max { p - max } else { 0.0 }
+    };
+
+    let mut best = generate_gun(num_traits, seed);
+    for i in 1..100 {
+        if distance(&best) == 0.0 {
+            break;
+        }
+        let candidate = generate_gun(num_traits, khash(seed.wrapping_add(i)));
+        if distance(&candidate) < distance(&best) {
+            best = candidate;
+        }
     }
+    best
+}
+
+// crafting: child gets both parents genomes, anything whose dependency didnt make it gets dropped
+pub fn fuse_guns(a: &Gun, b: &Gun) -> Gun {
+    let mut traits = a.gun_traits.clone();
+    traits.extend(b.gun_traits.iter());
+    Gun::from_traits(&traits)
+        .with_ammo_type(a.ammo_type)
+}
+
+// swap one trait out for a new legal one, anything that depended on the old one goes too
+pub fn mutate_gun(gun: &Gun, mut seed: u32) -> Gun {
+    let mut traits = gun.gun_traits.clone();
+    let removed = if traits.is_empty() {
+        None
+    } else {
+        Some(traits.remove(khash(seed) as usize % traits.len()))
+    };
+    let mut traits = Gun::from_traits(&traits).gun_traits;
 
-    return g;
-}
\ No newline at end of file
+    for _ in 0..100 {
+        seed = khash(seed.wrapping_add(1));
+        let gt = random_gun_trait(seed);
+        if Some(gt) != removed && trait_ok(gt, &traits) {
+            traits.push(gt);
+            break;
+        }
+    }
+
+    Gun::from_traits(&traits)
+        .with_ammo_type(gun.ammo_type)
+}
+
+// whats in a but not b, counting stacks
+#[cfg(test)]
+fn missing_from(a: &[GunTrait], b: &[GunTrait]) -> Vec<GunTrait> {
+    let mut rest = b.to_vec();
+    a.iter().filter(|gt| match rest.iter().position(|other| other == *gt) {
+        Some(i) => { rest.remove(i); false },
+        None => true,
+    }).cloned().collect()
+}
+
+#[test]
+fn test_mutate_gun() {
+    for seed in 0..200 {
+        let gun = generate_gun(3, seed);
+        let mutant = mutate_gun(&gun, seed);
+        assert_eq!(mutant.gun_traits, mutate_gun(&gun, seed).gun_traits);
+        assert_eq!(Gun::from_traits(&mutant.gun_traits).gun_traits, mutant.gun_traits);
+        for (i, gt) in mutant.gun_traits.iter().enumerate() {
+            assert!(trait_ok(*gt, &mutant.gun_traits[..i]), "{:?}", mutant.gun_traits);
+        }
+
+        // one out and one different one in, unless something else depended on the one that went
+        let lost = missing_from(&gun.gun_traits, &mutant.gun_traits);
+        let gained = missing_from(&mutant.gun_traits, &gun.gun_traits);
+        let has_dependents = gun.gun_traits.iter().any(|gt| !gt.rule().requires.is_empty());
+        if !has_dependents {
+            assert_eq!(lost.len(), 1, "{:?} -> {:?}", gun.gun_traits, mutant.gun_traits);
+            assert_eq!(gained.len(), 1, "{:?} -> {:?}", gun.gun_traits, mutant.gun_traits);
+            assert_ne!(lost, gained);
+        }
+    }
+
+    // losing double burst takes heavy burst with it
+    let burst = Gun::from_traits(&[GunTrait::DoubleBurst, GunTrait::HeavyBurst]);
+    for seed in 0..20 {
+        let mutant = mutate_gun(&burst, seed);
+        assert!(!mutant.gun_traits.contains(&GunTrait::HeavyBurst) || mutant.gun_traits.contains(&GunTrait::DoubleBurst));
+    }
+}
+
+#[test]
+fn test_heavy_burst_without_burst() {
+    let mut gun = Gun::new_default();
+    gun.apply_trait(GunTrait::HeavyBurst);
+    assert_eq!(gun.action, Action::Auto);
+}
+
+#[test]
+fn test_trait_costs() {
+    assert_eq!(Gun::new_default().cost, Cost::Free);
+    assert_eq!(Gun::from_traits(&[GunTrait::Windup]).cost, Cost::Mana(0.25));
+    match Gun::from_traits(&[GunTrait::Hungry, GunTrait::Hungry]).cost {
+        Cost::Blood(amount) => assert!((amount - 0.06).abs() < 0.001),
+        cost => panic!("{:?}", cost),
+    }
+}
+
+#[test]
+fn test_falloff_composes() {
+    let shotgun = Gun::from_traits(&[GunTrait::Shotgun]);
+    let both = Gun::from_traits(&[GunTrait::Shotgun, GunTrait::Marksman]);
+    let sawnoff = Gun::from_traits(&[GunTrait::Shotgun, GunTrait::Sawnoff]);
+    assert!(both.falloff > 0.0 && both.falloff < shotgun.falloff);
+    assert!(sawnoff.falloff > shotgun.falloff && sawnoff.falloff <= 0.9);
+}
+
+#[test]
+fn test_fuse_guns() {
+    let burst = Gun::from_traits(&[GunTrait::HeavyBurst, GunTrait::DoubleBurst]);
+    let shotgun = Gun::from_traits(&[GunTrait::Shotgun]);
+    let child = fuse_guns(&burst, &shotgun);
+    assert_eq!(child.gun_traits, vec![GunTrait::DoubleBurst, GunTrait::HeavyBurst, GunTrait::Shotgun]);
+    assert_eq!(child.action, Action::Burst(6, 0.7 * 2.0));
+
+    // orphaned heavy burst shouldnt survive without its double burst
+    let mut orphan = Gun::new_default();
+    orphan.gun_traits.push(GunTrait::HeavyBurst);
+    let child = fuse_guns(&orphan, &shotgun);
+    assert_eq!(child.gun_traits, vec![GunTrait::Shotgun]);
+}
+
+#[test]
+fn test_overheat() {
+    let mut gun = Gun::new(1.0, 0.05, 1.0, 0.0, 0).with_heat(0.25, 0.5);
+    let dt = 0.1;
+    let mut t = 0.0;
+    let mut shots = 0;
+    while !gun.state.overheated {
+        let will_shoot = gun.will_shoot(true, t);
+        gun.update(true, will_shoot, t, dt);
+        if will_shoot { shots += 1; }
+        t += dt;
+    }
+    assert_eq!(shots, 5); // bleeding 0.05 a tick
+    assert!(!gun.will_shoot(true, t));
+
+    // bleeds off and unlocks once fully cool
+    for _ in 0..25 {
+        gun.update(false, false, t, dt);
+        t += dt;
+    }
+    assert!(!gun.state.overheated);
+    assert!(gun.will_shoot(true, t));
+}
+
+#[test]
+fn test_reload() {
+    let mut gun = Gun::new(1.0, 0.05, 1.0, 0.0, 10).with_magazine(4, 0.5);
+    assert_eq!((gun.state.ammo, gun.state.reserve), (4, 6));
+    let dt = 0.1;
+    let mut t = 0.0;
+    for _ in 0..4 {
+        let will_shoot = gun.will_shoot(true, t);
+        assert!(will_shoot);
+        gun.update(true, will_shoot, t, dt);
+        t += dt;
+    }
+
+    // empty mag reloads itself and cant shoot in the meantime
+    assert!(gun.state.reloading);
+    assert!(!gun.will_shoot(true, t));
+    assert_eq!(gun.move_speed_multiplier(), 0.5);
+
+    t += 0.5;
+    gun.update(false, false, t, dt);
+    assert!(!gun.state.reloading);
+    assert_eq!((gun.state.ammo, gun.state.reserve), (4, 2));
+}
+
+#[cfg(test)]
+fn hold_and_release(gun: &mut Gun, hold_time: f32) {
+    let dt = 0.05;
+    let mut t = 0.0;
+    while t < hold_time {
+        let will_shoot = gun.will_shoot(true, t);
+        assert!(!will_shoot);
+        gun.update(true, will_shoot, t, dt);
+        t += dt;
+    }
+    let will_shoot = gun.will_shoot(false, t);
+    assert!(will_shoot);
+    gun.update(false, will_shoot, t, dt);
+}
+
+#[test]
+fn test_charge_early_release() {
+    let mut gun = Gun::new(1.0, 0.1, 1.0, 0.0, 10).with_multishot(2, 0.5).with_charge(1.0);
+    hold_and_release(&mut gun, 0.2);
+    assert!(gun.state.charge < 0.3);
+    let (damage, speed, count) = gun.shot_profile();
+    assert!(damage < 1.0);
+    assert!(speed < 1.0);
+    assert_eq!(count, 1);
+    assert!(!gun.state.charging);
+}
+
+#[test]
+fn test_charge_full() {
+    let mut gun = Gun::new(1.0, 0.1, 1.0, 0.0, 10).with_multishot(2, 0.5).with_charge(1.0);
+    hold_and_release(&mut gun, 3.0);
+    assert_eq!(gun.state.charge, 1.0);
+    let (damage, speed, count) = gun.shot_profile();
+    assert!((damage - 2.0).abs() < 0.001);
+    assert!((speed - 1.4).abs() < 0.001);
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn test_generate_gun_deterministic() {
+    for seed in [0, 1, 1234, 987654321] {
+        assert_eq!(generate_gun(3, seed).gun_traits, generate_gun(3, seed).gun_traits);
+    }
+    assert_eq!(generate_gun(3, 1234).gun_traits, vec![GunTrait::Manual, GunTrait::Ricochet, GunTrait::Piercing]);
+}
+
+#[test]
+fn test_power_band() {
+    for rarity in [Rarity::Common, Rarity::Rare, Rarity::Epic] {
+        for seed in 0..20 {
+            let gun = generate_gun_in_band(3, rarity.power_band(), seed);
+            assert_eq!(gun.rarity(), rarity);
+        }
+    }
+}
+
+#[test]
+fn test_power_ignores_loaded_ammo() {
+    let mut gun = Gun::new_burstrifle();
+    let fresh = gun.power();
+    gun.state.ammo = 0;
+    gun.state.reserve = 3;
+    assert_eq!(gun.power(), fresh);
+}
+
+#[test]
+fn test_trait_rules() {
+    for seed in 0..1000 {
+        let traits = generate_gun(4, seed).gun_traits;
+        for (i, gt) in traits.iter().enumerate() {
+            let rule = gt.rule();
+            let before = &traits[..i];
+            assert!(rule.requires.iter().all(|req| before.contains(req)), "{:?}", traits);
+            assert!(!traits.iter().any(|other| rule.excludes.contains(other)), "{:?}", traits);
+            assert!(traits.iter().filter(|other| *other == gt).count() <= rule.max_stack, "{:?}", traits);
+        }
+    }
+
+    // crafting doesnt get to break the rules either
+    let manual = Gun::from_traits(&[GunTrait::Manual]);
+    let spray = Gun::from_traits(&[GunTrait::SprayNPray, GunTrait::SprayNPray]);
+    assert_eq!(spray.gun_traits, vec![GunTrait::SprayNPray, GunTrait::SprayNPray]);
+    assert_eq!(fuse_guns(&manual, &spray).gun_traits, vec![GunTrait::Manual]);
+    assert_eq!(fuse_guns(&spray, &spray).gun_traits, vec![GunTrait::SprayNPray, GunTrait::SprayNPray]);
+
+    // fused deliveries dont get traits that would do nothing for them
+    for fused in [GunTrait::Grenade, GunTrait::Mine, GunTrait::Sticky] {
+        for useless in [GunTrait::Piercing, GunTrait::Lightning] {
+            assert!(!trait_ok(useless, &[fused]));
+            assert!(!trait_ok(fused, &[useless]));
+            assert_eq!(Gun::from_traits(&[fused, useless]).gun_traits.len(), 1);
+        }
+    }
+    assert!(!trait_ok(GunTrait::Explosive, &[GunTrait::Grenade]));
+    assert!(!trait_ok(GunTrait::Grenade, &[GunTrait::Explosive]));
+}
+#[test]
+fn test_patterns() {
+    let dir = Vec2::new(1.0, 0.0);
+    assert_eq!(Pattern::Ring(8).emitters(dir, 0.0).len(), 8);
+
+    // spiral turns as the trigger is held
+    let a = Pattern::Spiral(2, 1.0).emitters(dir, 0.0);
+    let b = Pattern::Spiral(2, 1.0).emitters(dir, 0.5);
+    assert!((a[0].angle_to(b[0]) - 0.5).abs() < 0.001);
+    assert!((a[0].angle_to(a[1]).abs() - std::f32::consts::PI).abs() < 0.001);
+}
+
+#[test]
+fn test_roll_damage() {
+    for seed in 0..100 {
+        let a = roll_damage(1.0, 0.2, 0.3, 3.0, seed);
+        let b = roll_damage(1.0, 0.2, 0.3, 3.0, seed);
+        assert_eq!(a.amount, b.amount);
+        assert_eq!(a.crit, b.crit);
+        let base = if a.crit { a.amount / 3.0 } else { a.amount };
+        assert!((0.8..=1.2).contains(&base));
+    }
+    let crits = (0..1000).filter(|seed| roll_damage(1.0, 0.0, 0.3, 2.0, *seed).crit).count();
+    assert!(crits > 250 && crits < 350);
+    assert!((0..100).all(|seed| !roll_damage(1.0, 0.0, 0.0, 2.0, seed).crit));
+}
@@ -0,0 +1,143 @@
+use crate::gun::*;
+use crate::entity::*;
+use crate::kmath::*;
+
+// headless gun runner for balancing traits, pulls the trigger in a pattern and counts what comes out
+
+#[derive(Debug, Clone, Copy)]
+pub enum TriggerPattern {
+    Hold,
+    Tap(f32),           // click every this many seconds
+    BurstTap(f32, f32), // hold for this long, let go for this long
+}
+
+impl TriggerPattern {
+    fn squeeze(&self, t: f32, dt: f32) -> bool {
+        match *self {
+            TriggerPattern::Hold => true,
+            TriggerPattern::Tap(interval) => t % interval < dt,
+            TriggerPattern::BurstTap(on, off) => t % (on + off) < on,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimReport {
+    pub shots: i32,
+    pub bullets: i32,
+    pub total_damage: f32,
+    pub dps: f32,
+    pub time_to_empty: Option<f32>,
+}
+
+pub fn simulate(gun: &Gun, pattern: TriggerPattern, duration: f32, dt: f32) -> SimReport {
+    let mut gun = gun.clone();
+    let mut bullets = Vec::new();
+
+    let mut report = SimReport {
+        shots: 0,
+        bullets: 0,
+        total_damage: 0.0,
+        dps: 0.0,
+        time_to_empty: None,
+    };
+
+    let ticks = (duration / dt) as i32;
+    for tick in 0..ticks {
+        let t = tick as f32 * dt;
+        let squeeze = pattern.squeeze(t, dt);
+
+        let will_shoot = gun.will_shoot(squeeze, t);
+        gun.update(squeeze, will_shoot, t, dt);
+        if will_shoot {
+            bullets.clear();
+            gun.make_bullets(&mut bullets, Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 0, EntityTeam::Player, t);
+            report.shots += 1;
+            report.bullets += bullets.len() as i32;
+            report.total_damage += bullets.iter().map(|b| b.damage).sum::<f32>();
+        }
+
+        if report.time_to_empty.is_none() && gun.feed == Feed::Ammo && gun.state.ammo + gun.state.reserve <= 0 {
+            report.time_to_empty = Some(t);
+        }
+    }
+
+    report.dps = report.total_damage / duration;
+    report
+}
+
+// whatever generate_gun could roll: each trait has to be allowed by the ones before it, stacks included.
+// requirements come earlier in ALL_GUN_TRAITS so never going backwards still reaches every set, once
+fn trait_combos(max_traits: usize, start: usize, current: &mut Vec<GunTrait>, combos: &mut Vec<Vec<GunTrait>>) {
+    if !current.is_empty() {
+        combos.push(current.clone());
+    }
+    if current.len() == max_traits {
+        return;
+    }
+    for (i, gt) in ALL_GUN_TRAITS.iter().enumerate().skip(start) {
+        if !trait_ok(*gt, current) {
+            continue;
+        }
+        current.push(*gt);
+        trait_combos(max_traits, i, current, combos);
+        current.pop();
+    }
+}
+
+// every set of up to max_traits traits a gun could roll, skipping any where a trait would get dropped
+pub fn trait_balance_table(max_traits: usize, pattern: TriggerPattern, duration: f32, dt: f32) -> Vec<(Vec<GunTrait>, SimReport)> {
+    let mut combos = Vec::new();
+    trait_combos(max_traits, 0, &mut Vec::new(), &mut combos);
+
+    combos.iter()
+        .map(|combo| (combo.len(), Gun::from_traits(combo)))
+        .filter(|(len, gun)| gun.gun_traits.len() == *len)
+        .map(|(_, gun)| (gun.gun_traits.clone(), simulate(&gun, pattern, duration, dt)))
+        .collect()
+}
+
+#[test]
+fn test_simulate_default() {
+    // 0.7s cooldown auto doing 2 a shot, 10 round mag and 20 in reserve, 1s reloads
+    let report = simulate(&Gun::new_default(), TriggerPattern::Hold, 30.0, 1.0 / 60.0);
+    assert_eq!(report.shots, 30);
+    assert_eq!(report.bullets, 30);
+    assert!((report.total_damage - 60.0).abs() < 0.001);
+    assert!(report.time_to_empty.is_some());
+
+    // charge guns never fire if you never let go
+    let charge = Gun::new_default().with_charge(1.0);
+    assert_eq!(simulate(&charge, TriggerPattern::Hold, 5.0, 1.0 / 60.0).shots, 0);
+    assert!(simulate(&charge, TriggerPattern::BurstTap(1.0, 0.5), 5.0, 1.0 / 60.0).shots > 0);
+}
+
+#[test]
+fn test_trait_balance_table() {
+    let dt = 1.0 / 60.0;
+    for pattern in [TriggerPattern::Hold, TriggerPattern::Tap(0.25), TriggerPattern::BurstTap(1.0, 0.5)] {
+        println!("{:?}", pattern);
+        println!("{:<50} {:>6} {:>8} {:>8} {:>6} {:>8}", "traits", "shots", "bullets", "damage", "dps", "empty");
+        let table = trait_balance_table(3, pattern, 10.0, dt);
+        assert!(!table.is_empty());
+        // stacks and full three trait guns are in there too
+        assert!(table.iter().any(|(traits, _)| traits == &vec![GunTrait::Rapidfire, GunTrait::Rapidfire]));
+        assert!(table.iter().any(|(traits, _)| traits.len() == 3));
+        for (traits, report) in table.iter() {
+            for (i, gt) in traits.iter().enumerate() {
+                assert!(trait_ok(*gt, &traits[..i]), "{:?}", traits);
+            }
+            let power = Gun::from_traits(traits).power();
+            assert!(power.is_finite() && power > 0.0, "{:?} {}", traits, power);
+            println!("{:<50} {:>6} {:>8} {:>8.1} {:>6.2} {:>8}",
+                format!("{:?}", traits), report.shots, report.bullets, report.total_damage, report.dps,
+                report.time_to_empty.map(|t| format!("{:.1}", t)).unwrap_or("-".to_string()));
+        }
+    }
+
+    // rapidfire held down: 0.28s cooldown, so a 20 round mag lasts 5.6s, a 1s reload, then 13 more
+    let table = trait_balance_table(1, TriggerPattern::Hold, 10.0, dt);
+    let (_, rapid) = table.iter().find(|(traits, _)| traits == &vec![GunTrait::Rapidfire]).unwrap();
+    assert_eq!((rapid.shots, rapid.bullets), (33, 33));
+    assert!((rapid.total_damage - 33.0 * 1.4).abs() < 0.01);
+}
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use crate::gun::*;
+
+// how the guns youre carrying come back out, picked once per run
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discipline {
+    Fifo,   // oldest first
+    Stack,  // newest first
+    Ring,   // whichever is selected, scroll to pick
+}
+
+impl Discipline {
+    pub fn from_name(name: &str) -> Option<Discipline> {
+        match name {
+            "fifo" => Some(Discipline::Fifo),
+            "stack" => Some(Discipline::Stack),
+            "ring" => Some(Discipline::Ring),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GunInventory {
+    pub discipline: Discipline,
+    guns: VecDeque<Gun>,
+    selected: usize,
+}
+
+impl GunInventory {
+    pub fn new(discipline: Discipline) -> GunInventory {
+        GunInventory {
+            discipline,
+            guns: VecDeque::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn push(&mut self, gun: Gun) {
+        self.guns.push_back(gun);
+    }
+
+    // index of the gun take would give you
+    pub fn next_index(&self) -> Option<usize> {
+        if self.guns.is_empty() {
+            return None;
+        }
+        Some(match self.discipline {
+            Discipline::Fifo => 0,
+            Discipline::Stack => self.guns.len() - 1,
+            Discipline::Ring => self.selected,
+        })
+    }
+
+    pub fn take(&mut self) -> Option<Gun> {
+        let gun = self.guns.remove(self.next_index()?);
+        if self.selected >= self.guns.len() {
+            self.selected = 0;
+        }
+        gun
+    }
+
+    // only the ring cares, the others always give you the same one
+    pub fn cycle(&mut self, amount: i32) {
+        if self.discipline != Discipline::Ring || self.guns.is_empty() {
+            return;
+        }
+        let n = self.guns.len() as i32;
+        self.selected = (self.selected as i32 + amount).rem_euclid(n) as usize;
+    }
+
+    pub fn len(&self) -> usize {
+        self.guns.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Gun> {
+        self.guns.iter()
+    }
+}
+
+#[cfg(test)]
+fn test_inventory(discipline: Discipline) -> GunInventory {
+    let mut inv = GunInventory::new(discipline);
+    inv.push(Gun::new_machinegun());
+    inv.push(Gun::new_shotgun());
+    inv.push(Gun::new_burstrifle());
+    inv
+}
+
+#[test]
+fn test_inventory_fifo() {
+    let mut inv = test_inventory(Discipline::Fifo);
+    inv.cycle(1);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 3);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 5);
+    inv.push(Gun::new_machinegun());
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 1);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 3);
+    assert!(inv.take().is_none());
+}
+
+#[test]
+fn test_inventory_stack() {
+    let mut inv = test_inventory(Discipline::Stack);
+    inv.cycle(1);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 1);
+    inv.push(Gun::new_machinegun());
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 3);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 5);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 3);
+    assert!(inv.take().is_none());
+}
+
+#[test]
+fn test_inventory_ring() {
+    let mut inv = test_inventory(Discipline::Ring);
+    inv.cycle(-1);
+    assert_eq!(inv.next_index(), Some(2));
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 1);
+    // fell off the end so it wraps back to the start
+    assert_eq!(inv.next_index(), Some(0));
+    inv.cycle(3);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 5);
+    assert_eq!(inv.take().unwrap().bullets_per_shot, 3);
+    assert!(inv.take().is_none());
+    inv.cycle(1);
+    assert_eq!(inv.next_index(), None);
+}
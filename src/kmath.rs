@@ -20,6 +20,11 @@ impl Vec2 {
         ) 
     }
     
+    pub fn dot(&self, other: Vec2) -> f32 { self.x*other.x + self.y*other.y }
+    pub fn cross(&self, other: Vec2) -> f32 { self.x*other.y - self.y*other.x }
+    // signed, in the same direction as rotate
+    pub fn angle_to(&self, other: Vec2) -> f32 { self.cross(other).atan2(self.dot(other)) }
+
     pub fn spread(&self, amount: f32) -> Vec2 {
         if amount == 0.0 {return *self};
         let roll = rand::thread_rng().gen_range(-amount..amount);
@@ -76,9 +81,9 @@ impl std::ops::Neg for Vec2 {
 }
 
 pub fn khash(mut state: u32) -> u32 {
-    state = (state ^ 2747636419) * 2654435769;
-    state = (state ^ (state >> 16)) * 2654435769;
-    state = (state ^ (state >> 16)) * 2654435769;
+    state = (state ^ 2747636419).wrapping_mul(2654435769);
+    state = (state ^ (state >> 16)).wrapping_mul(2654435769);
+    state = (state ^ (state >> 16)).wrapping_mul(2654435769);
     state
 }
 
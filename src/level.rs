@@ -1,11 +1,15 @@
 use glam::Vec3;
 use std::collections::HashMap;
 use crate::entity::*;
+use crate::gun::*;
+use crate::status::*;
 use crate::rect::*;
 use rand::prelude::*;
 use crate::kmath::*;
 use crate::map_gen::*;
 use crate::map_fragment::*;
+use crate::collision_system::*;
+use crate::ordnance::*;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub struct Tile {
@@ -142,11 +146,12 @@ impl Level {
             let walker_pos_x = x as f32 * level.grid_size + level.grid_size as f32/2.0;
             let walker_pos_y = y as f32 * level.grid_size + level.grid_size as f32/2.0;
 
-            let entity_kinds = vec!(EntityKind::WalkerShooter, EntityKind::RunnerGunner, EntityKind::Chungus, EntityKind::GunPickup);
+            let entity_kinds = vec!(EntityKind::WalkerShooter, EntityKind::RunnerGunner, EntityKind::Chungus, EntityKind::Rusher, EntityKind::GunPickup, EntityKind::SpellPage);
 
-            level.entities.insert(rand::thread_rng().gen(), Entity::new(
+            level.entities.insert(rand::thread_rng().gen(), Entity::new_seeded(
                 entity_kinds[rand::thread_rng().gen_range(0..entity_kinds.len())], 
-                Vec2::new(walker_pos_x, walker_pos_y)));
+                Vec2::new(walker_pos_x, walker_pos_y),
+                rand::thread_rng().gen()));
         }
 
         // CA pass
@@ -285,8 +290,17 @@ impl Level {
                     if krand(s) < 0.08 {
                         let px = i as f32 * level.grid_size + level.grid_size as f32/2.0;
                         let py = j as f32 * level.grid_size + level.grid_size as f32/2.0;
-                        let entity_kinds = vec!(EntityKind::WalkerShooter, EntityKind::RunnerGunner, EntityKind::Chungus, EntityKind::GunPickup);
-                        let e = Entity::new(entity_kinds[khash(s + 2) as usize % entity_kinds.len()], Vec2::new(px, py));
+                        let entity_kinds = vec!(EntityKind::WalkerShooter, EntityKind::RunnerGunner, EntityKind::Chungus, EntityKind::Rusher, EntityKind::GunPickup, EntityKind::SpellPage);
+                        let mut e = Entity::new_seeded(entity_kinds[khash(s + 2) as usize % entity_kinds.len()], Vec2::new(px, py), khash(s + 3));
+                        if e.kind == EntityKind::GunPickup {
+                            let roll = krand(s + 4);
+                            let rarity = match roll {
+                                r if r > 0.9 => Rarity::Epic,
+                                r if r > 0.6 => Rarity::Rare,
+                                _ => Rarity::Common,
+                            };
+                            e.gun = generate_gun_in_band(3, rarity.power_band(), khash(s + 3));
+                        }
                         level.entities.insert(khash(s + 1), e);
                     }
                 }
@@ -320,20 +334,363 @@ impl Level {
         match command {
             EntityCommand::Move(id, dir) => {
                 if let Some(ent) = self.entities.get_mut(&id) {
-                    ent.velocity = ent.speed * dir;
+                    ent.velocity = ent.speed * ent.move_speed_multiplier() * dir + ent.knockback;
                 }},
-            EntityCommand::Shoot(id, dir) => {
+            EntityCommand::Aim(id, dir) => {
+                if let Some(ent) = self.entities.get_mut(&id) {
+                ent.aim_dir = dir;
+            }},
+            EntityCommand::Shoot(id) => {
                 if let Some(ent) = self.entities.get_mut(&id) {
                 ent.want_shoot = true;
-                ent.previous_shoot_dir = dir;
             }},
             EntityCommand::Unshoot(id) => {
                 if let Some(ent) = self.entities.get_mut(&id) {
                 ent.want_shoot = false;
             }},
+            EntityCommand::Reload(id) => {
+                if let Some(ent) = self.entities.get_mut(&id) {
+                ent.want_reload = true;
+            }},
+            EntityCommand::Melee(id) => {
+                if let Some(ent) = self.entities.get_mut(&id) {
+                ent.want_melee = true;
+            }},
+        }
+    }
+
+    // damage falls off linearly to nothing at the edge, doesnt hurt your own team except maybe the owner
+    pub fn explode(&mut self, pos: Vec2, radius: f32, damage: f32, team: EntityTeam, owner: u32, self_damage: bool) {
+        for (id, ent) in self.entities.iter_mut() {
+            if ent.kind == EntityKind::Bullet || ent.kind.is_pickup() {
+                continue;
+            }
+            if ent.team == team && !(self_damage && *id == owner) {
+                continue;
+            }
+            let dist = (ent.aabb.centroid() - pos).magnitude();
+            if dist < radius {
+                ent.apply_damage(damage * (1.0 - dist / radius));
+            }
         }
     }
 
+    // hitscan, shot is the bullet the gun wouldve fired. goes until the first wall and hits the first
+    // entity on the way, or a few more if it pierces. returns where the beam stopped and who it hit for how much
+    pub fn fire_beam(&mut self, shot: &Entity, seed: u32) -> (Vec2, Vec<(u32, DamageRoll)>) {
+        let range = shot.range;
+
+        let origin = shot.aabb.centroid();
+        let dir = shot.velocity.normalize();
+        let mut end = origin + dir * range;
+        if let Some(wall) = self.raycast(origin, end) {
+            end = wall;
+        }
+
+        let mut along: Vec<(u32, f32)> = self.entities.iter()
+            .filter(|(id, ent)| **id != shot.owner && ent.team != shot.team && ent.health > 0.0)
+            .filter(|(_, ent)| ent.kind != EntityKind::Bullet && !ent.kind.is_pickup())
+            .filter_map(|(id, ent)| segment_rect_intersection(origin, end, ent.aabb).map(|t| (*id, t)))
+            .collect();
+        along.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        along.truncate(shot.pierce as usize + 1);
+
+        // doesnt go through the last thing it hit
+        if along.len() == shot.pierce as usize + 1 {
+            end = origin.lerp(end, along[along.len() - 1].1);
+        }
+
+        let length = (end - origin).magnitude();
+        let mut hits = Vec::new();
+        for (id, t) in along {
+            if let Some(ent) = self.entities.get_mut(&id) {
+                let roll = shot.roll_hit(t * length, khash(seed ^ id));
+                ent.apply_damage(roll.amount);
+                if let Some(effect) = shot.ammo_type.on_hit() {
+                    ent.add_status(effect);
+                }
+                hits.push((id, roll));
+            }
+        }
+
+        (end, hits)
+    }
+
+    // everyone with their finger on the trigger, shots go wherever theyre aiming right now
+    pub fn fire_guns(&mut self, t: f32, dt: f32, bullets: &mut Vec<Entity>, beams: &mut Vec<Entity>) {
+        // by id rather than hashmap order, so shots come out (and get numbered for damage rolls) the same every run
+        let mut ids: Vec<u32> = self.entities.keys().copied().collect();
+        ids.sort_unstable();
+        for entity_key in ids {
+            let entity = match self.entities.get_mut(&entity_key) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            if entity.want_reload {
+                entity.gun.start_reload(t);
+                entity.want_reload = false;
+            }
+            let squeeze = entity.want_shoot && !entity.has_status(StatusKind::Stunned);
+            let will_shoot = entity.gun.will_shoot_for(entity, squeeze, t);
+            entity.gun.update(squeeze, will_shoot, t, dt);
+            if will_shoot {
+                // heat boost is from before paying for this shot
+                let boost = entity.resources.power_multiplier();
+                entity.pay(entity.gun.cost);
+                let shots = match entity.gun.delivery {
+                    Delivery::Beam => &mut *beams,
+                    _ => &mut *bullets,
+                };
+                let first = shots.len();
+                entity.gun.make_bullets(shots, entity.aabb.centroid(), entity.aim_dir, entity_key, entity.team, t);
+                for shot in shots[first..].iter_mut() {
+                    shot.damage *= boost;
+                }
+            }
+        }
+    }
+
+    // bullets run out of range, and anything that leaves the map is gone
+    pub fn expire_bullets(&mut self, dt: f32) {
+        let level_w = self.grid_size * self.side_length as f32;
+        for bullet in self.entities.values_mut().filter(|e| e.kind == EntityKind::Bullet) {
+            bullet.travelled += bullet.velocity.magnitude() * dt;
+            let pos = bullet.aabb.centroid();
+            let off_map = pos.x < 0.0 || pos.y < 0.0 || pos.x > level_w || pos.y > level_w;
+            // fused stuff stops on its own and has its own timer
+            let out_of_range = bullet.fuse.is_none() && bullet.travelled > bullet.range;
+            if off_map || out_of_range {
+                bullet.health = 0.0;
+            }
+        }
+    }
+
+    // age fused projectiles and decide which ones go off, stuck ones ride along with their host
+    // returns the blasts in the same shape game queues explosions
+    pub fn tick_fuses(&mut self, dt: f32) -> Vec<(Vec2, f32, f32, EntityTeam, u32, bool)> {
+        let targets: Vec<(u32, Vec2, EntityTeam)> = self.entities.iter()
+            .filter(|(_, e)| e.kind != EntityKind::Bullet && !e.kind.is_pickup() && e.health > 0.0)
+            .map(|(id, e)| (*id, e.aabb.centroid(), e.team))
+            .collect();
+
+        let mut detonations = Vec::new();
+        for bullet in self.entities.values_mut() {
+            let fuse = match bullet.fuse.as_mut() {
+                Some(fuse) if bullet.health > 0.0 => fuse,
+                _ => continue,
+            };
+            fuse.age += dt;
+            bullet.velocity = bullet.velocity * fuse.drag().powf(dt);
+
+            if let Some((host, offset)) = fuse.stuck_to {
+                match targets.iter().find(|(id, _, _)| *id == host) {
+                    Some((_, host_pos, _)) => {
+                        let c = *host_pos + offset;
+                        bullet.aabb = Rect::new_centered(c.x, c.y, bullet.aabb.w, bullet.aabb.h);
+                    },
+                    None => fuse.stuck_to = None,   // host died, just drops where it is
+                }
+            }
+
+            let pos = bullet.aabb.centroid();
+            let go_off = match fuse.kind {
+                FuseKind::Grenade | FuseKind::Sticky => fuse.lit(),
+                FuseKind::Mine(radius) => fuse.age > MINE_LIFETIME || (fuse.lit() && targets.iter()
+                    .any(|(_, p, team)| *team != bullet.team && (*p - pos).magnitude() < radius)),
+            };
+            if go_off {
+                bullet.health = 0.0;
+                let radius = if bullet.explosion_radius > 0.0 { bullet.explosion_radius } else { DEFAULT_BLAST_RADIUS };
+                detonations.push((pos, radius, bullet.damage, bullet.team, bullet.owner, bullet.self_damage));
+            }
+        }
+        detonations
+    }
+
+    // fused projectiles dont hurt on contact, grenades pop, mines stop and stickies latch on
+    pub fn fuse_contact(&mut self, id: u32, object: CollisionObject) {
+        let host_pos = match object {
+            CollisionObject::Entity(host) => self.entities.get(&host).map(|e| (host, e.aabb.centroid())),
+            CollisionObject::Terrain(..) => None,
+        };
+        let bullet = match self.entities.get_mut(&id) {
+            Some(bullet) => bullet,
+            None => return,
+        };
+        let pos = bullet.aabb.centroid();
+        if let Some(fuse) = bullet.fuse.as_mut() {
+            match (fuse.kind, host_pos) {
+                (FuseKind::Grenade, Some(_)) => fuse.age = fuse.fuse,
+                (FuseKind::Sticky, Some((host, host_pos))) if fuse.stuck_to.is_none() => {
+                    fuse.stuck_to = Some((host, pos - host_pos));
+                },
+                _ => {},
+            }
+            bullet.velocity = Vec2::new(0.0, 0.0);
+        }
+    }
+
+    // start swings that were asked for, and hit whoever is in the arc of an active one
+    pub fn tick_melee(&mut self, t: f32, dt: f32) {
+        let knockback_decay = 0.001_f32;
+
+        let mut swings = Vec::new();
+        for (id, ent) in self.entities.iter_mut() {
+            ent.knockback = ent.knockback * knockback_decay.powf(dt);
+            let stunned = ent.has_status(StatusKind::Stunned);
+            if let Some(melee) = ent.melee.as_mut() {
+                if ent.want_melee && !stunned {
+                    melee.swing(ent.aim_dir, t);
+                }
+                melee.update(t);
+                if melee.active(t) {
+                    swings.push((*id, ent.aabb.centroid(), ent.team));
+                }
+            }
+            ent.want_melee = false;
+        }
+
+        for (attacker_id, origin, team) in swings {
+            let melee = match self.entities.get(&attacker_id).and_then(|e| e.melee.clone()) {
+                Some(melee) => melee,
+                None => continue,
+            };
+            let targets: Vec<u32> = self.entities.iter()
+                .filter(|(id, ent)| ent.team != team && ent.health > 0.0 && !melee.state.hit_ids.contains(id))
+                .filter(|(_, ent)| ent.kind != EntityKind::Bullet && !ent.kind.is_pickup())
+                .filter(|(_, ent)| melee.in_arc(origin, ent.aabb.centroid()))
+                .map(|(id, _)| *id)
+                .collect();
+
+            for id in targets.iter() {
+                if let Some(ent) = self.entities.get_mut(id) {
+                    ent.apply_damage(melee.damage);
+                    ent.knockback = (ent.aabb.centroid() - origin).normalize() * melee.knockback;
+                }
+            }
+            if let Some(m) = self.entities.get_mut(&attacker_id).and_then(|e| e.melee.as_mut()) {
+                m.state.hit_ids.extend(targets);
+            }
+        }
+    }
+
+    // ticks everyones status effects, poison also jumps to anyone close enough on the same team
+    // and wards eat enemy bullets
+    pub fn tick_status(&mut self, dt: f32) {
+        let spread_radius = 0.15;
+
+        let ward_radius = 0.12;
+
+        let wards: Vec<(Vec2, EntityTeam)> = self.entities.values()
+            .filter(|e| e.has_status(StatusKind::Warded))
+            .map(|e| (e.aabb.centroid(), e.team))
+            .collect();
+        for bullet in self.entities.values_mut().filter(|e| e.kind == EntityKind::Bullet) {
+            let pos = bullet.aabb.centroid();
+            if wards.iter().any(|(p, team)| *team != bullet.team && (*p - pos).magnitude() < ward_radius) {
+                bullet.health = 0.0;
+            }
+        }
+
+        let poisoned: Vec<(Vec2, EntityTeam, f32)> = self.entities.values()
+            .filter_map(|e| e.status.iter()
+                .find(|s| s.kind == StatusKind::Poisoned && s.remaining > 1.0)
+                .map(|s| (e.aabb.centroid(), e.team, s.remaining)))
+            .collect();
+
+        for ent in self.entities.values_mut() {
+            if ent.kind == EntityKind::Bullet || ent.kind.is_pickup() {
+                continue;
+            }
+            if !ent.has_status(StatusKind::Poisoned) {
+                let pos = ent.aabb.centroid();
+                let source = poisoned.iter()
+                    .find(|(p, team, _)| *team == ent.team && (*p - pos).magnitude() < spread_radius);
+                if let Some((_, _, remaining)) = source {
+                    // weaker each time so it dies out
+                    ent.add_status(StatusEffect { kind: StatusKind::Poisoned, remaining: remaining * 0.5 });
+                }
+            }
+            ent.tick_status(dt);
+            ent.resources.tick(dt);
+        }
+    }
+
+    // homing bullets turn toward the nearest enemy in front of them, but only so fast
+    pub fn steer_homing(&mut self, dt: f32) {
+        let cone = 0.6;
+        let range = 1.0;
+
+        let mut steers = Vec::new();
+        for (id, bullet) in self.entities.iter().filter(|(_, e)| e.kind == EntityKind::Bullet && e.homing > 0.0) {
+            let pos = bullet.aabb.centroid();
+            let heading = bullet.velocity;
+            if heading.magnitude() == 0.0 {
+                continue;
+            }
+
+            let target = self.entities.values()
+                .filter(|e| e.team != bullet.team && e.health > 0.0)
+                .filter(|e| e.kind != EntityKind::Bullet && !e.kind.is_pickup())
+                .map(|e| e.aabb.centroid() - pos)
+                .filter(|d| d.magnitude() < range && heading.angle_to(*d).abs() < cone)
+                .min_by(|a, b| a.magnitude().partial_cmp(&b.magnitude()).unwrap());
+
+            if let Some(d) = target {
+                let max_turn = bullet.homing * dt;
+                let turn = heading.angle_to(d).max(-max_turn).min(max_turn);
+                steers.push((*id, heading.rotate(turn)));
+            }
+        }
+
+        for (id, velocity) in steers {
+            if let Some(bullet) = self.entities.get_mut(&id) {
+                bullet.velocity = velocity;
+            }
+        }
+    }
+
+    // lightning jumps from whoever got hit to the nearest enemy it can see, weaker each jump
+    // returns the arcs so they can be drawn
+    pub fn chain_lightning(&mut self, first_hit: u32, damage: f32, jumps: i32, team: EntityTeam) -> Vec<(Vec2, Vec2)> {
+        let range = 0.4;
+        let decay = 0.6;
+
+        let mut arcs = Vec::new();
+        let mut hit = vec![first_hit];
+        let mut from = match self.entities.get(&first_hit) {
+            Some(ent) => ent.aabb.centroid(),
+            None => return arcs,
+        };
+        let mut arc_damage = damage;
+
+        for _ in 0..jumps {
+            arc_damage *= decay;
+
+            let next = self.entities.iter()
+                .filter(|(id, ent)| !hit.contains(id) && ent.team != team && ent.health > 0.0)
+                .filter(|(_, ent)| ent.kind != EntityKind::Bullet && !ent.kind.is_pickup())
+                .map(|(id, ent)| (*id, ent.aabb.centroid()))
+                .filter(|(_, pos)| (*pos - from).magnitude() < range)
+                .filter(|(_, pos)| self.raycast(from, *pos).is_none())
+                .min_by(|(_, a), (_, b)| (*a - from).magnitude().partial_cmp(&(*b - from).magnitude()).unwrap());
+
+            match next {
+                Some((id, pos)) => {
+                    if let Some(ent) = self.entities.get_mut(&id) {
+                        ent.apply_damage(arc_damage);
+                    }
+                    arcs.push((from, pos));
+                    hit.push(id);
+                    from = pos;
+                },
+                None => break,
+            }
+        }
+
+        arcs
+    }
+
     pub fn raycast(&self, ray_origin: Vec2, ray_destination: Vec2) -> Option<Vec2> {
         let round_up = |u: f32, side_length: f32| {
             (u/side_length).ceil() * side_length
@@ -486,4 +843,195 @@ pub fn test_raycast2() {
     assert_eq!(level.raycast(Vec2::new(1.1, 1.1), Vec2::new(7.1, 1.1)), Some(Vec2::new(7.0, 1.1)));
     assert_eq!(level.raycast(Vec2::new(1.1, 1.1), Vec2::new(7.1, 7.1)), Some(Vec2::new(5.0, 5.0)));
 }
-*/
\ No newline at end of file
+*/
+// 1x1 of open floor, nothing in it
+#[cfg(test)]
+fn open_level() -> Level {
+    let open = Tile {walkable: true, overhang: false, underhang: false, edge: false};
+    Level {
+        entities: HashMap::new(),
+        tiles: vec!(open; 25),
+        side_length: 5,
+        grid_size: 0.2,
+        floor_colour: Vec3::new(0.0, 0.0, 0.0),
+        wall_colour: Vec3::new(0.0, 0.0, 0.0),
+    }
+}
+
+#[test]
+fn test_homing_turn_rate() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Bullet, Vec2::new(0.1, 0.5))
+        .with_velocity(Vec2::new(1.0, 0.0))
+        .with_team(EntityTeam::Player)
+        .with_homing(1.0));
+    level.entities.insert(2, Entity::new(EntityKind::WalkerShooter, Vec2::new(0.6, 0.7)));
+
+    level.steer_homing(0.1);
+    let v = level.entities.get(&1).unwrap().velocity;
+    assert!((Vec2::new(1.0, 0.0).angle_to(v) - 0.1).abs() < 0.0001);
+
+    // same team, not a target
+    level.entities.get_mut(&2).unwrap().team = EntityTeam::Player;
+    level.steer_homing(0.1);
+    assert_eq!(level.entities.get(&1).unwrap().velocity, v);
+}
+
+#[test]
+fn test_fuses() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Bullet, Vec2::new(0.1, 0.1))
+        .with_velocity(Vec2::new(1.0, 0.0))
+        .with_team(EntityTeam::Player)
+        .with_fuse(Some(Fuse::new(FuseKind::Grenade, 0.5))));
+    level.entities.insert(2, Entity::new(EntityKind::Bullet, Vec2::new(0.5, 0.5))
+        .with_team(EntityTeam::Player)
+        .with_fuse(Some(Fuse::new(FuseKind::Mine(0.15), 0.2))));
+    level.entities.insert(3, Entity::new(EntityKind::Bullet, Vec2::new(0.5, 0.8))
+        .with_team(EntityTeam::Player)
+        .with_fuse(Some(Fuse::new(FuseKind::Sticky, 1.0))));
+    level.entities.insert(4, Entity::new(EntityKind::WalkerShooter, Vec2::new(0.9, 0.5)));
+
+    // grenade slows down but hasnt gone off, mine isnt armed yet
+    assert!(level.tick_fuses(0.3).is_empty());
+    assert!(level.entities.get(&1).unwrap().velocity.x < 1.0);
+
+    // grenade goes off, mine is armed but nobodys near it
+    assert_eq!(level.tick_fuses(0.3).len(), 1);
+
+    // walker wanders onto the mine
+    level.entities.get_mut(&4).unwrap().aabb = Rect::new_centered(0.55, 0.5, 0.05, 0.05);
+    assert_eq!(level.tick_fuses(0.1).len(), 1);
+
+    // sticky follows the walker around
+    level.fuse_contact(3, CollisionObject::Entity(4));
+    level.entities.get_mut(&4).unwrap().aabb = Rect::new_centered(0.55, 0.9, 0.05, 0.05);
+    level.tick_fuses(0.1);
+    let sticky = level.entities.get(&3).unwrap().aabb.centroid();
+    assert!((sticky - Vec2::new(0.5, 1.2)).magnitude() < 0.001);
+}
+
+#[test]
+fn test_expire_bullets() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Bullet, Vec2::new(0.5, 0.5))
+        .with_velocity(Vec2::new(0.0, 1.0))
+        .with_damage(1.0)
+        .with_range(0.3, 0.5));
+    level.entities.insert(2, Entity::new(EntityKind::Bullet, Vec2::new(-0.1, 0.5)));
+
+    level.expire_bullets(0.2);
+    let bullet = level.entities.get(&1).unwrap();
+    assert!(bullet.health > 0.0);
+    assert!((bullet.damage_at(bullet.travelled) - 0.6666).abs() < 0.001);
+    assert!(level.entities.get(&2).unwrap().health <= 0.0);
+
+    level.expire_bullets(0.2);
+    assert!(level.entities.get(&1).unwrap().health <= 0.0);
+}
+
+#[test]
+fn test_aim_separate_from_trigger() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Player, Vec2::new(0.5, 0.5)));
+
+    level.apply_command(EntityCommand::Shoot(1));
+    level.apply_command(EntityCommand::Aim(1, Vec2::new(0.0, 1.0)));
+    level.apply_command(EntityCommand::Unshoot(1));
+    // letting go doesnt stop the aim updating
+    level.apply_command(EntityCommand::Aim(1, Vec2::new(-1.0, 0.0)));
+    let player = level.entities.get(&1).unwrap();
+    assert!(!player.want_shoot);
+    assert_eq!(player.aim_dir, Vec2::new(-1.0, 0.0));
+}
+
+#[test]
+fn test_burst_follows_aim() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Player, Vec2::new(0.5, 0.5)));
+    let mut bullets = Vec::new();
+    let mut beams = Vec::new();
+    let dt = 0.01;
+    let mut t = 0.0;
+
+    // tap the trigger, then swing round while the rest of the burst comes out
+    level.apply_command(EntityCommand::Aim(1, Vec2::new(1.0, 0.0)));
+    level.apply_command(EntityCommand::Shoot(1));
+    level.fire_guns(t, dt, &mut bullets, &mut beams);
+    assert_eq!(bullets.len(), 1);
+    level.apply_command(EntityCommand::Unshoot(1));
+    level.apply_command(EntityCommand::Aim(1, Vec2::new(0.0, 1.0)));
+    while bullets.len() < 3 && t < 1.0 {
+        t += dt;
+        level.fire_guns(t, dt, &mut bullets, &mut beams);
+    }
+
+    assert_eq!(bullets.len(), 3);
+    assert!(bullets[0].velocity.normalize().x > 0.9);
+    for bullet in bullets[1..].iter() {
+        assert!(bullet.velocity.normalize().y > 0.9);
+    }
+}
+
+#[test]
+fn test_ai_aim_on_top_of_target() {
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::Player, Vec2::new(0.5, 0.5)));
+    // no direction to aim in, so they keep the old one rather than going NaN
+    for kind in [EntityKind::WalkerShooter, EntityKind::RunnerGunner, EntityKind::Rusher] {
+        let enemy = Entity::new(kind, Vec2::new(0.5, 0.5));
+        let mut commands = Vec::new();
+        enemy.think(2, &level, &mut commands, 0.0);
+        assert!(!commands.iter().any(|c| matches!(c, EntityCommand::Aim(..))));
+    }
+}
+
+#[test]
+fn test_resting_ordnance_doesnt_block() {
+    let dt = 1.0 / 60.0;
+
+    // walker with a sticky on its left side, walking left
+    let mut level = open_level();
+    level.entities.insert(1, Entity::new(EntityKind::WalkerShooter, Vec2::new(0.5, 0.5))
+        .with_velocity(Vec2::new(-0.2, 0.0)));
+    let mut sticky = Fuse::new(FuseKind::Sticky, 10.0);
+    sticky.stuck_to = Some((1, Vec2::new(-0.03, 0.0)));
+    level.entities.insert(2, Entity::new(EntityKind::Bullet, Vec2::new(0.47, 0.5))
+        .with_team(EntityTeam::Player)
+        .with_fuse(Some(sticky)));
+    // walker walking over an unarmed mine
+    level.entities.insert(3, Entity::new(EntityKind::WalkerShooter, Vec2::new(0.5, 0.2))
+        .with_velocity(Vec2::new(-0.2, 0.0)));
+    level.entities.insert(4, Entity::new(EntityKind::Bullet, Vec2::new(0.45, 0.2))
+        .with_team(EntityTeam::Player)
+        .with_fuse(Some(Fuse::new(FuseKind::Mine(0.15), 10.0))));
+
+    for _ in 0..30 {
+        let mut collisions = Vec::new();
+        collide_entity_entity(&level.entities, &mut collisions, dt);
+        apply_movement(&mut level.entities, &collisions, dt);
+        level.tick_fuses(dt);
+    }
+
+    // both get the full 0.2 * 0.5s
+    for id in [1, 3] {
+        let x = level.entities.get(&id).unwrap().aabb.centroid().x;
+        assert!((x - 0.4).abs() < 0.005, "{} {}", id, x);
+    }
+    // and the sticky came along
+    let sticky_x = level.entities.get(&2).unwrap().aabb.centroid().x;
+    assert!((sticky_x - 0.37).abs() < 0.005);
+}
+
+#[test]
+fn test_fire_guns_in_id_order() {
+    let mut level = open_level();
+    for id in [40, 7, 300, 12, 9001, 1] {
+        level.entities.insert(id, Entity::new(EntityKind::Player, Vec2::new(0.5, 0.5)));
+        level.apply_command(EntityCommand::Shoot(id));
+    }
+    let mut bullets = Vec::new();
+    level.fire_guns(0.0, 0.01, &mut bullets, &mut Vec::new());
+    let owners: Vec<u32> = bullets.iter().map(|b| b.owner).collect();
+    assert_eq!(owners, vec![1, 7, 12, 40, 300, 9001]);
+}
@@ -4,10 +4,19 @@ mod rect;
 mod entity;
 mod collision_system;
 mod gun;
+#[cfg(test)]
+mod gun_sim;
 mod kmath;
 mod map_gen;
 mod map_fragment;
 mod priority_queue;
+mod effect;
+mod status;
+mod melee;
+mod spell;
+mod resource;
+mod inventory;
+mod ordnance;
 
 mod level;
 
@@ -84,10 +93,13 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         gl.clear_color(0.0, 0.0, 0.0, 1.0);
 
-        let mut game = Game::new(window_x / window_y);
+        // pick how the gun queue works for this run, eg cargo run -- stack
+        let discipline = std::env::args().nth(1)
+            .and_then(|arg| inventory::Discipline::from_name(&arg))
+            .unwrap_or(inventory::Discipline::Fifo);
+        let mut game = Game::new(window_x / window_y, discipline);
 
         let mut held_keys: HashSet<glutin::event::VirtualKeyCode> = HashSet::new();
-        let mut lmb = false;
         let mut normalized_cursor_pos = Vec2::new(0.0, 0.0);
         let mut dt = 1.0f64 / 60f64;
 
@@ -204,6 +216,24 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 (glutin::event::VirtualKeyCode::R, glutin::event::ElementState::Released) => {
                                     game.apply_command(InputCommand::Reset)
                                 },
+                                (glutin::event::VirtualKeyCode::C, glutin::event::ElementState::Released) => {
+                                    game.apply_command(InputCommand::CraftGun)
+                                },
+                                (glutin::event::VirtualKeyCode::V, glutin::event::ElementState::Released) => {
+                                    game.apply_command(InputCommand::MutateGun)
+                                },
+                                (glutin::event::VirtualKeyCode::Space, glutin::event::ElementState::Pressed) => {
+                                    game.apply_command(InputCommand::Reload)
+                                },
+                                (glutin::event::VirtualKeyCode::F, glutin::event::ElementState::Pressed) => {
+                                    game.apply_command(InputCommand::Melee)
+                                },
+                                (glutin::event::VirtualKeyCode::E, glutin::event::ElementState::Pressed) => {
+                                    game.apply_command(InputCommand::NextPage)
+                                },
+                                (glutin::event::VirtualKeyCode::Q, glutin::event::ElementState::Pressed) => {
+                                    game.apply_command(InputCommand::PrevPage)
+                                },
                             _ => (),
                         }},
                         WindowEvent::MouseInput {
@@ -213,16 +243,33 @@ fn main() -> Result<(), Box<dyn Error>> {
                         } => {
                             game.apply_command(InputCommand::EatGun);
                         }
+                        WindowEvent::MouseInput {
+                            button: glutin::event::MouseButton::Middle,
+                            state: glutin::event::ElementState::Pressed,
+                            ..
+                        } => {
+                            game.apply_command(InputCommand::CastSpell(normalized_cursor_pos));
+                        }
+                        WindowEvent::MouseWheel {
+                            delta,
+                            ..
+                        } => {
+                            let amount = match delta {
+                                glutin::event::MouseScrollDelta::LineDelta(_, y) => *y,
+                                glutin::event::MouseScrollDelta::PixelDelta(p) => p.y as f32,
+                            };
+                            if amount != 0.0 {
+                                game.apply_command(InputCommand::CycleGun(if amount > 0.0 { -1 } else { 1 }));
+                            }
+                        }
                         WindowEvent::MouseInput {
                             button: glutin::event::MouseButton::Left,
                             state:state,
                             ..
                         } => {
                             if *state == glutin::event::ElementState::Pressed {
-                                lmb = true;
-                                game.apply_command(InputCommand::Shoot(normalized_cursor_pos));
+                                game.apply_command(InputCommand::Shoot);
                             } else {
-                                lmb = false;
                                 game.apply_command(InputCommand::Unshoot);
                             }
                         },
@@ -235,9 +282,6 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 pos.y as f32 / window_y);
 
                             game.apply_command(InputCommand::Look(normalized_cursor_pos));
-                            if lmb {
-                                game.apply_command(InputCommand::Shoot(normalized_cursor_pos));
-                            }
                         },
                         _ => (),
                     },
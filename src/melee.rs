@@ -0,0 +1,94 @@
+use crate::kmath::*;
+
+// close range attack, hits anything in an arc in front of you while the swing is active
+
+#[derive(Debug, Clone)]
+pub struct MeleeState {
+    pub swing_start: f32,
+    pub swinging: bool,
+    pub dir: Vec2,
+    pub hit_ids: Vec<u32>,  // each swing only gets each entity once
+}
+
+#[derive(Debug, Clone)]
+pub struct Melee {
+    pub damage: f32,
+    pub reach: f32,
+    pub arc: f32,           // half angle
+    pub active_time: f32,
+    pub cooldown: f32,      // from the start of one swing to the next
+    pub knockback: f32,
+
+    pub state: MeleeState,
+}
+
+impl Melee {
+    pub fn new(damage: f32, reach: f32, arc: f32, active_time: f32, cooldown: f32, knockback: f32) -> Melee {
+        Melee {
+            damage,
+            reach,
+            arc,
+            active_time,
+            cooldown,
+            knockback,
+            state: MeleeState {
+                swing_start: -999.0,
+                swinging: false,
+                dir: Vec2::new(1.0, 0.0),
+                hit_ids: Vec::new(),
+            },
+        }
+    }
+
+    pub fn new_knife() -> Melee {
+        Melee::new(1.5, 0.1, 1.0, 0.12, 0.4, 1.0)
+    }
+
+    pub fn new_claws() -> Melee {
+        Melee::new(1.0, 0.08, 0.8, 0.15, 0.8, 0.6)
+    }
+
+    pub fn swing(&mut self, dir: Vec2, t: f32) -> bool {
+        if t - self.state.swing_start < self.cooldown {
+            return false;
+        }
+        self.state.swing_start = t;
+        self.state.swinging = true;
+        self.state.dir = dir;
+        self.state.hit_ids.clear();
+        true
+    }
+
+    pub fn active(&self, t: f32) -> bool {
+        self.state.swinging && t - self.state.swing_start <= self.active_time
+    }
+
+    pub fn update(&mut self, t: f32) {
+        if self.state.swinging && !self.active(t) {
+            self.state.swinging = false;
+        }
+    }
+
+    pub fn in_arc(&self, origin: Vec2, target: Vec2) -> bool {
+        let d = target - origin;
+        if d.magnitude() > self.reach {
+            return false;
+        }
+        d.magnitude() == 0.0 || self.state.dir.angle_to(d).abs() <= self.arc
+    }
+}
+
+#[test]
+fn test_melee_swing() {
+    let mut m = Melee::new_knife();
+    let origin = Vec2::new(0.0, 0.0);
+    assert!(m.swing(Vec2::new(1.0, 0.0), 1.0));
+    assert!(!m.swing(Vec2::new(1.0, 0.0), 1.1));
+    assert!(m.active(1.05));
+    assert!(m.in_arc(origin, Vec2::new(0.08, 0.02)));
+    assert!(!m.in_arc(origin, Vec2::new(-0.05, 0.0)));
+    assert!(!m.in_arc(origin, Vec2::new(0.5, 0.0)));
+    m.update(1.2);
+    assert!(!m.active(1.2));
+    assert!(m.swing(Vec2::new(0.0, 1.0), 1.5));
+}
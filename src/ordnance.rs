@@ -0,0 +1,59 @@
+use glam::Vec3;
+use crate::kmath::*;
+
+// projectiles that go off on a timer instead of on contact: grenades, mines and sticky bombs
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FuseKind {
+    Grenade,        // slows down and goes off when the fuse runs out
+    Mine(f32),      // arms after the fuse, then goes off when an enemy gets inside the radius
+    Sticky,         // latches on to whatever it hits, goes off when the fuse runs out
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fuse {
+    pub kind: FuseKind,
+    pub age: f32,
+    pub fuse: f32,
+    pub stuck_to: Option<(u32, Vec2)>,    // host and offset from its centre
+}
+
+pub const MINE_LIFETIME: f32 = 20.0;
+pub const DEFAULT_BLAST_RADIUS: f32 = 0.15;
+
+impl Fuse {
+    pub fn new(kind: FuseKind, fuse: f32) -> Fuse {
+        Fuse {
+            kind,
+            age: 0.0,
+            fuse,
+            stuck_to: None,
+        }
+    }
+
+    pub fn lit(&self) -> bool {
+        self.age >= self.fuse
+    }
+
+    // fraction of speed kept per second
+    pub fn drag(&self) -> f32 {
+        match self.kind {
+            FuseKind::Grenade => 0.2,
+            FuseKind::Mine(_) => 0.005,
+            FuseKind::Sticky => 1.0,
+        }
+    }
+
+    // blinks faster the closer it is to going off, mines just blink once theyre armed
+    pub fn colour(&self) -> Vec3 {
+        let blink_rate = match self.kind {
+            FuseKind::Mine(_) => if self.lit() { 4.0 } else { 0.0 },
+            _ => 2.0 + 10.0 * (self.age / self.fuse).min(1.0),
+        };
+        if (self.age * blink_rate).fract() > 0.5 {
+            Vec3::new(1.0, 0.1, 0.1)
+        } else {
+            Vec3::new(0.2, 0.2, 0.2)
+        }
+    }
+}
@@ -122,6 +122,21 @@ impl Renderer {
         self.push_triangle(Triangle3{ a: v1, b: v3, c: v2 });
     }
 
+    pub fn draw_line(&mut self, a: Vec2, b: Vec2, thickness: f32, colour: Vec3, depth: f32) {
+        let d = b - a;
+        if d.magnitude() == 0.0 {
+            return;
+        }
+        let normal = Vec2::new(-d.y, d.x).normalize() * (thickness / 2.0);
+        let corners = [a + normal, b + normal, b - normal, a - normal];
+        let verts: Vec<Vert3> = corners.iter().map(|p| Vert3 {
+            pos: Vec3::new(p.x, p.y, depth),
+            colour,
+        }).collect();
+        self.push_triangle(Triangle3{ a: verts[0], b: verts[1], c: verts[2] });
+        self.push_triangle(Triangle3{ a: verts[0], b: verts[2], c: verts[3] });
+    }
+
     pub fn clear(&mut self) {
         self.triangles.clear();
     }
@@ -0,0 +1,81 @@
+// what it costs to fire a gun or cast a spell
+// mana comes back on its own, blood comes out of your health, heat builds up and
+// makes you hit harder the closer you are to overflowing
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cost {
+    Free,
+    Mana(f32),
+    Blood(f32),
+    Heat(f32),
+}
+
+impl Cost {
+    // stacking costs from several traits. same kind adds up, otherwise the first one sticks
+    pub fn plus(self, other: Cost) -> Cost {
+        match (self, other) {
+            (Cost::Free, c) | (c, Cost::Free) => c,
+            (Cost::Mana(a), Cost::Mana(b)) => Cost::Mana(a + b),
+            (Cost::Blood(a), Cost::Blood(b)) => Cost::Blood(a + b),
+            (Cost::Heat(a), Cost::Heat(b)) => Cost::Heat(a + b),
+            (c, _) => c,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resources {
+    pub mana: f32,
+    pub max_mana: f32,
+    pub mana_regen: f32,        // per second
+    pub heat: f32,
+    pub max_heat: f32,
+    pub heat_dissipation: f32,  // per second
+}
+
+impl Resources {
+    pub fn new() -> Resources {
+        Resources {
+            mana: 1.0,
+            max_mana: 1.0,
+            mana_regen: 0.2,
+            heat: 0.0,
+            max_heat: 1.0,
+            heat_dissipation: 0.15,
+        }
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.mana = (self.mana + self.mana_regen * dt).min(self.max_mana);
+        self.heat = (self.heat - self.heat_dissipation * dt).max(0.0);
+    }
+
+    // nothing until halfway, then ramps up to double at critical
+    pub fn power_multiplier(&self) -> f32 {
+        let h = self.heat / self.max_heat;
+        1.0 + (2.0 * h - 1.0).clamp(0.0, 1.0)
+    }
+}
+
+#[test]
+fn test_cost_plus() {
+    assert_eq!(Cost::Free.plus(Cost::Mana(0.25)), Cost::Mana(0.25));
+    assert_eq!(Cost::Blood(0.25).plus(Cost::Blood(0.25)), Cost::Blood(0.5));
+    assert_eq!(Cost::Heat(0.25).plus(Cost::Mana(0.25)), Cost::Heat(0.25));
+}
+
+#[test]
+fn test_resources_tick() {
+    let mut r = Resources::new();
+    r.mana = 0.0;
+    r.heat = 0.5;
+    assert_eq!(r.power_multiplier(), 1.0);
+    r.heat = 0.75;
+    assert!((r.power_multiplier() - 1.5).abs() < 0.001);
+    r.tick(1.0);
+    assert!((r.mana - 0.2).abs() < 0.001);
+    assert!((r.heat - 0.6).abs() < 0.001);
+    r.tick(10.0);
+    assert_eq!(r.mana, r.max_mana);
+    assert_eq!(r.heat, 0.0);
+}
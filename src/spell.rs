@@ -0,0 +1,131 @@
+use glam::Vec3;
+use crate::kmath::*;
+use crate::resource::*;
+
+// the magic side of things, find pages and flip through your book to pick what to cast
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpellKind {
+    Projectile,   // slow bolt that blows up
+    Beam,         // hitscan, pierces a couple
+    Ward,         // eats enemy bullets around you for a bit
+    Aoe,          // blast centred on you
+    Blink,        // short teleport toward the cursor
+}
+
+#[derive(Debug, Clone)]
+pub struct Spell {
+    pub kind: SpellKind,
+    pub power: f32,
+    pub cooldown: f32,
+    pub cost: Cost,
+    pub last_cast: f32,
+}
+
+impl Spell {
+    pub fn new(kind: SpellKind, power: f32) -> Spell {
+        let (cooldown, cost) = match kind {
+            SpellKind::Projectile => (0.5, Cost::Mana(0.15)),
+            SpellKind::Beam => (0.8, Cost::Heat(0.3)),
+            SpellKind::Ward => (6.0, Cost::Mana(0.5)),
+            SpellKind::Aoe => (3.0, Cost::Blood(1.0)),
+            SpellKind::Blink => (1.5, Cost::Mana(0.25)),
+        };
+        Spell {
+            kind,
+            power,
+            cooldown,
+            cost,
+            last_cast: -999.0,
+        }
+    }
+
+    pub fn random(seed: u32) -> Spell {
+        let kind = match khash(seed) % 5 {
+            0 => SpellKind::Projectile,
+            1 => SpellKind::Beam,
+            2 => SpellKind::Ward,
+            3 => SpellKind::Aoe,
+            _ => SpellKind::Blink,
+        };
+        Spell::new(kind, 0.8 + 0.4 * krand(seed.wrapping_add(1)))
+    }
+
+    pub fn ready(&self, t: f32) -> bool {
+        t - self.last_cast >= self.cooldown
+    }
+
+    pub fn colour(&self) -> Vec3 {
+        match self.kind {
+            SpellKind::Projectile => Vec3::new(1.0, 0.3, 0.1),
+            SpellKind::Beam => Vec3::new(1.0, 0.2, 0.8),
+            SpellKind::Ward => Vec3::new(0.7, 0.9, 1.0),
+            SpellKind::Aoe => Vec3::new(1.0, 0.8, 0.1),
+            SpellKind::Blink => Vec3::new(0.4, 0.2, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spellbook {
+    pub pages: Vec<Spell>,
+    pub current: usize,
+}
+
+impl Spellbook {
+    pub fn new() -> Spellbook {
+        Spellbook {
+            pages: Vec::new(),
+            current: 0,
+        }
+    }
+
+    pub fn add_page(&mut self, spell: Spell) {
+        self.pages.push(spell);
+    }
+
+    // flipping wraps around
+    pub fn next_page(&mut self) {
+        if !self.pages.is_empty() {
+            self.current = (self.current + 1) % self.pages.len();
+        }
+    }
+
+    pub fn prev_page(&mut self) {
+        if !self.pages.is_empty() {
+            self.current = (self.current + self.pages.len() - 1) % self.pages.len();
+        }
+    }
+
+    pub fn current(&self) -> Option<&Spell> {
+        self.pages.get(self.current)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Spell> {
+        self.pages.get_mut(self.current)
+    }
+}
+
+#[test]
+fn test_spellbook_flip() {
+    let mut book = Spellbook::new();
+    book.next_page();
+    book.prev_page();
+    assert!(book.current().is_none());
+
+    book.add_page(Spell::new(SpellKind::Projectile, 1.0));
+    book.add_page(Spell::new(SpellKind::Ward, 1.0));
+    book.add_page(Spell::new(SpellKind::Blink, 1.0));
+    assert_eq!(book.current().unwrap().kind, SpellKind::Projectile);
+    book.prev_page();
+    assert_eq!(book.current().unwrap().kind, SpellKind::Blink);
+    book.next_page();
+    book.next_page();
+    assert_eq!(book.current().unwrap().kind, SpellKind::Ward);
+}
+
+#[test]
+fn test_random_spell_any_seed() {
+    let spell = Spell::random(u32::MAX);
+    assert!(spell.power >= 0.8 && spell.power <= 1.2);
+}
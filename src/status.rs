@@ -0,0 +1,85 @@
+use glam::Vec3;
+
+// ammo types put a status effect on whatever they hit, entities tick them every frame
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmmoType {
+    Standard,
+    Incendiary, // burns
+    Cryo,       // slows
+    Shock,      // brief stun
+    Toxic,      // poison that spreads to whoevers nearby
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusKind {
+    Burning,
+    Chilled,
+    Stunned,
+    Poisoned,
+    Warded,     // enemy bullets that get close just disappear
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub remaining: f32,
+}
+
+impl AmmoType {
+    pub fn on_hit(&self) -> Option<StatusEffect> {
+        let (kind, duration) = match self {
+            AmmoType::Standard => return None,
+            AmmoType::Incendiary => (StatusKind::Burning, 3.0),
+            AmmoType::Cryo => (StatusKind::Chilled, 2.0),
+            AmmoType::Shock => (StatusKind::Stunned, 0.4),
+            AmmoType::Toxic => (StatusKind::Poisoned, 4.0),
+        };
+        Some(StatusEffect { kind, remaining: duration })
+    }
+
+    pub fn colour(&self) -> Vec3 {
+        match self {
+            AmmoType::Standard => Vec3::new(1.0, 1.0, 0.0),
+            AmmoType::Incendiary => Vec3::new(1.0, 0.4, 0.0),
+            AmmoType::Cryo => Vec3::new(0.5, 0.9, 1.0),
+            AmmoType::Shock => Vec3::new(0.9, 0.9, 1.0),
+            AmmoType::Toxic => Vec3::new(0.3, 1.0, 0.2),
+        }
+    }
+}
+
+impl StatusKind {
+    pub fn damage_per_second(&self) -> f32 {
+        match self {
+            StatusKind::Burning => 1.0,
+            StatusKind::Poisoned => 0.5,
+            _ => 0.0,
+        }
+    }
+
+    pub fn move_speed_multiplier(&self) -> f32 {
+        match self {
+            StatusKind::Chilled => 0.5,
+            StatusKind::Stunned => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    pub fn tint(&self) -> Vec3 {
+        match self {
+            StatusKind::Burning => Vec3::new(1.0, 0.4, 0.0),
+            StatusKind::Chilled => Vec3::new(0.5, 0.9, 1.0),
+            StatusKind::Stunned => Vec3::new(1.0, 1.0, 0.6),
+            StatusKind::Poisoned => Vec3::new(0.3, 1.0, 0.2),
+            StatusKind::Warded => Vec3::new(0.7, 0.9, 1.0),
+        }
+    }
+}
+
+pub fn status_tint(colour: Vec3, status: &[StatusEffect]) -> Vec3 {
+    match status.first() {
+        Some(effect) => colour.lerp(effect.kind.tint(), 0.6),
+        None => colour,
+    }
+}